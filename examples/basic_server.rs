@@ -1,11 +1,15 @@
 extern crate flep;
 
 use flep::fs::FileSystem;
+use flep::rustls;
+use std::env;
 use std::path::Path;
+use std::sync::Arc;
 
 pub struct Server
 {
     file_system: flep::fs::Memory,
+    tls_config: Option<Arc<rustls::ServerConfig>>,
 }
 
 impl flep::server::Server for Server
@@ -19,6 +23,10 @@ impl flep::server::Server for Server
     fn file_system_mut(&mut self) -> &mut flep::fs::FileSystem {
         &mut self.file_system
     }
+
+    fn tls_config(&self) -> Option<Arc<rustls::ServerConfig>> {
+        self.tls_config.clone()
+    }
 }
 
 fn main() {
@@ -29,7 +37,16 @@ fn main() {
     file_system.write_file(&Path::new("README.txt"),
                            "hello there\nit is me".as_bytes().to_owned()).unwrap();
 
-    let mut server = Server { file_system: file_system };
+    // Pass a certificate and private key to support 'AUTH TLS'.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let tls_config = if args.len() == 2 {
+        Some(flep::io::tls::server_config(Path::new(&args[0]), Path::new(&args[1]))
+             .expect("could not load TLS certificate"))
+    } else {
+        None
+    };
+
+    let mut server = Server { file_system: file_system, tls_config: tls_config };
     flep::server::run(&mut server, "127.0.0.1:2222")
         .expect("error whilst running server");
 }
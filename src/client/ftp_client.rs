@@ -0,0 +1,784 @@
+use {Error, ErrorKind, FileType};
+use protocol::{self, Command, Reply};
+use protocol::reply::{code, Code};
+use protocol::facts::Entry;
+
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use std::thread;
+
+/// How long we wait for a data connection by default.
+const DEFAULT_DATA_TIMEOUT_SECS: u64 = 30;
+
+/// How often we check whether the server has connected in active mode.
+const ACCEPT_POLL_INTERVAL_MS: u64 = 10;
+
+/// How data connections are set up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DataMode
+{
+    /// We connect to the server, using 'EPSV', or 'PASV' if the
+    /// server does not support it.
+    Passive,
+    /// The server connects to us, using 'PORT' for IPv4 and 'EPRT'
+    /// for IPv6.
+    Active,
+}
+
+/// A blocking FTP client.
+///
+/// Transfers use whatever file type the server is set to. RFC 959
+/// makes that ASCII text, but many servers, including ours, start
+/// in binary, so call `set_file_type` if it matters.
+pub struct FtpClient
+{
+    /// The control connection.
+    control: BufReader<TcpStream>,
+    /// How data connections are set up.
+    data_mode: DataMode,
+    /// How long we wait for a data connection to be made.
+    data_timeout: Duration,
+    /// The offset the next transfer starts at.
+    restart_offset: Option<u64>,
+}
+
+/// A data connection that is being set up.
+enum PendingData
+{
+    /// We have connected to the server.
+    Connected(TcpStream),
+    /// We are waiting for the server to connect.
+    Listening(TcpListener),
+}
+
+impl FtpClient
+{
+    /// Connects to a server and waits until it is ready.
+    pub fn connect<A>(address: A) -> Result<Self, Error>
+        where A: ToSocketAddrs {
+        let stream = TcpStream::connect(address)?;
+        let mut client = FtpClient {
+            control: BufReader::new(stream),
+            data_mode: DataMode::Passive,
+            data_timeout: Duration::from_secs(DEFAULT_DATA_TIMEOUT_SECS),
+            restart_offset: None,
+        };
+
+        // The server may tell us to wait before it is ready.
+        loop {
+            let reply = client.read_reply()?;
+
+            if reply.code == code::SERVICE_READY_FOR_NEW_USER {
+                return Ok(client);
+            } else if reply.code != code::SERVICE_READY_ETA {
+                return Err(unexpected_reply(reply));
+            }
+        }
+    }
+
+    /// Logs in with a username and password.
+    ///
+    /// The password is only sent if the server asks for one.
+    pub fn login(&mut self, username: &str, password: &str) -> Result<(), Error> {
+        let pass = protocol::PASS { password: password.to_owned() };
+        command_line(&pass)?;
+
+        let reply = self.send_command(&protocol::USER { username: username.to_owned() })?;
+
+        if reply.code == code::USER_LOGGED_IN {
+            Ok(())
+        } else if reply.code == code::USER_NAME_OKAY_NEED_PASSWORD {
+            self.expect(&pass, &[code::USER_LOGGED_IN, code::COMMAND_NOT_IMPLEMENTED_SUPERFLOUS])?;
+            Ok(())
+        } else {
+            Err(unexpected_reply(reply))
+        }
+    }
+
+    /// Gets the current working directory.
+    pub fn pwd(&mut self) -> Result<String, Error> {
+        let reply = self.expect(&protocol::PWD, &[code::PATHNAME_CREATED])?;
+        let text = reply.text.to_string();
+
+        match parse_quoted_path(&text) {
+            Some(path) => Ok(path),
+            None => Err(ErrorKind::Protocol(protocol::ErrorKind::InvalidReply(
+                format!("'PWD' reply should contain a quoted path: '{}'", text.trim()))).into()),
+        }
+    }
+
+    /// Changes the current working directory.
+    pub fn cwd(&mut self, path: &str) -> Result<(), Error> {
+        self.expect(&protocol::CWD { path: path.to_owned() }, &[code::REQUESTED_FILE_ACTION_COMPLETED])?;
+        Ok(())
+    }
+
+    /// Changes to the parent of the current working directory.
+    pub fn cdup(&mut self) -> Result<(), Error> {
+        self.expect(&protocol::CDUP, &[code::REQUESTED_FILE_ACTION_COMPLETED, code::OK])?;
+        Ok(())
+    }
+
+    /// Gets how data connections are set up.
+    pub fn data_mode(&self) -> DataMode { self.data_mode }
+
+    /// Sets how data connections are set up.
+    pub fn set_data_mode(&mut self, data_mode: DataMode) {
+        self.data_mode = data_mode;
+    }
+
+    /// Sets how long we wait for a data connection to be made.
+    pub fn set_data_timeout(&mut self, timeout: Duration) {
+        self.data_timeout = timeout;
+    }
+
+    /// Sets the file type used for transfers.
+    pub fn set_file_type(&mut self, file_type: FileType) -> Result<(), Error> {
+        self.expect(&protocol::TYPE { file_type: file_type }, &[code::OK])?;
+        Ok(())
+    }
+
+    /// Lists a directory in the server's own format, one line per file.
+    pub fn list(&mut self, path: Option<&str>) -> Result<Vec<String>, Error> {
+        self.read_lines(&protocol::LIST { remote_filespec: path.map(ToOwned::to_owned) })
+    }
+
+    /// Lists the names of the files in a directory.
+    pub fn nlst(&mut self, path: Option<&str>) -> Result<Vec<String>, Error> {
+        self.read_lines(&protocol::NLST { remote_directory: path.map(ToOwned::to_owned) })
+    }
+
+    /// Lists the facts about the files in a directory.
+    pub fn mlsd(&mut self, path: Option<&str>) -> Result<Vec<Entry>, Error> {
+        let lines = self.read_lines(&protocol::MLSD { remote_directory: path.map(ToOwned::to_owned) })?;
+
+        let entries: Result<Vec<Entry>, _> = lines.iter().map(|line| Entry::parse(line)).collect();
+        Ok(entries?)
+    }
+
+    /// Sets the offset the next `retrieve` or `store` starts at.
+    ///
+    /// Used to resume transfers that were interrupted. Servers only
+    /// accept 'REST' directly before the transfer, so it is sent then.
+    pub fn restart(&mut self, offset: u64) {
+        self.restart_offset = Some(offset);
+    }
+
+    /// Downloads a file.
+    ///
+    /// Returns the number of bytes received.
+    pub fn retrieve(&mut self, remote_filename: &str, output: &mut Write) -> Result<u64, Error> {
+        let offset = self.restart_offset.take();
+        let mut stream = self.start_transfer(&protocol::RETR { remote_filename: remote_filename.to_owned() },
+                                             offset)?;
+        let result = io::copy(&mut stream, output);
+        drop(stream);
+
+        self.finish_transfer(result)
+    }
+
+    /// Uploads a file.
+    ///
+    /// Returns the number of bytes sent.
+    pub fn store(&mut self, remote_filename: &str, input: &mut Read) -> Result<u64, Error> {
+        let offset = self.restart_offset.take();
+        let mut stream = self.start_transfer(&protocol::STOR { remote_filename: remote_filename.to_owned() },
+                                             offset)?;
+
+        // The server knows the file is complete when we close the connection.
+        let result = io::copy(input, &mut stream)
+            .and_then(|bytes| stream.shutdown(Shutdown::Write).map(|_| bytes));
+        drop(stream);
+
+        self.finish_transfer(result)
+    }
+
+    /// Renames a file.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let rnto = protocol::RNTO { to_filename: to.to_owned() };
+        command_line(&rnto)?;
+
+        self.expect(&protocol::RNFR { from_filename: from.to_owned() },
+                    &[code::REQUESTED_FILE_ACTION_PENDING_FURTHER_INFORMATION])?;
+        self.expect(&rnto, &[code::REQUESTED_FILE_ACTION_COMPLETED])?;
+        Ok(())
+    }
+
+    /// Deletes a file.
+    pub fn delete(&mut self, remote_filename: &str) -> Result<(), Error> {
+        self.expect(&protocol::DELE { remote_filename: remote_filename.to_owned() },
+                    &[code::REQUESTED_FILE_ACTION_COMPLETED])?;
+        Ok(())
+    }
+
+    /// Logs out and closes the connection.
+    pub fn quit(mut self) -> Result<(), Error> {
+        self.expect(&protocol::QUIT, &[code::SERVICE_CLOSING_CONTROL_CONNECTION])?;
+        Ok(())
+    }
+
+    /// Sends a command and reads the reply.
+    ///
+    /// Commands which open a data connection should not be sent
+    /// this way. Nothing is sent if the command has an argument that
+    /// can't be sent, such as a path with a line break in it.
+    pub fn send_command<C>(&mut self, command: &C) -> Result<Reply, Error>
+        where C: Command {
+        // Written in one go so that the command is sent in one packet.
+        let line = command_line(command)?;
+        self.control.get_mut().write_all(&line)?;
+
+        self.read_reply()
+    }
+
+    /// Reads a reply from the server.
+    fn read_reply(&mut self) -> Result<Reply, Error> {
+        Ok(Reply::read(&mut self.control)?)
+    }
+
+    /// Sends a command and checks the reply has one of the given codes.
+    fn expect<C>(&mut self, command: &C, codes: &[Code]) -> Result<Reply, Error>
+        where C: Command {
+        let reply = self.send_command(command)?;
+
+        if codes.contains(&reply.code) {
+            Ok(reply)
+        } else {
+            Err(unexpected_reply(reply))
+        }
+    }
+
+    /// Runs a transfer command and reads all of the lines it sends.
+    fn read_lines<C>(&mut self, command: &C) -> Result<Vec<String>, Error>
+        where C: Command {
+        let mut data = Vec::new();
+        self.retrieve_with(command, &mut data)?;
+
+        let text = String::from_utf8(data).map_err(protocol::Error::from)?;
+        Ok(text.lines().filter(|line| !line.is_empty()).map(ToOwned::to_owned).collect())
+    }
+
+    /// Runs a transfer command, writing what it sends to `output`.
+    fn retrieve_with<C>(&mut self, command: &C, output: &mut Write) -> Result<u64, Error>
+        where C: Command {
+        let mut stream = self.start_transfer(command, None)?;
+        let result = io::copy(&mut stream, output);
+        drop(stream);
+
+        self.finish_transfer(result)
+    }
+
+    /// Sets up a data connection and sends the command that uses it.
+    fn start_transfer<C>(&mut self, command: &C, offset: Option<u64>) -> Result<TcpStream, Error>
+        where C: Command {
+        // Checked before the data connection is opened for it.
+        command_line(command)?;
+
+        let pending = self.open_data_connection()?;
+
+        if let Some(offset) = offset {
+            self.expect(&protocol::REST { marker: offset },
+                        &[code::REQUESTED_FILE_ACTION_PENDING_FURTHER_INFORMATION])?;
+        }
+
+        self.expect(command, &[code::FILE_STATUS_OKAY,
+                               code::DATA_CONNECTION_ALREADY_OPEN_TRANSFER_STARTING])?;
+
+        match pending {
+            PendingData::Connected(stream) => Ok(stream),
+            PendingData::Listening(listener) => {
+                let (stream, peer_addr) = match accept(&listener, &self.control, self.data_timeout)? {
+                    Some(accepted) => accepted,
+                    // The server gave up trying to connect to us.
+                    None => return Err(unexpected_reply(self.read_reply()?)),
+                };
+
+                // Anybody could connect to the port we gave the server.
+                let server_ip = self.control.get_ref().peer_addr()?.ip();
+                if peer_addr.ip() != server_ip {
+                    return Err(format!("data connection came from {} instead of the server",
+                                       peer_addr.ip()).into());
+                }
+                Ok(stream)
+            },
+        }
+    }
+
+    /// Waits for the server to confirm the transfer has finished.
+    fn finish_transfer(&mut self, result: io::Result<u64>) -> Result<u64, Error> {
+        // The server replies even if the transfer failed, so we always
+        // read it to keep the control connection in sync.
+        let reply = self.read_reply()?;
+        let bytes = result?;
+
+        if reply.code == code::CLOSING_DATA_CONNECTION ||
+            reply.code == code::REQUESTED_FILE_ACTION_COMPLETED {
+            Ok(bytes)
+        } else {
+            Err(unexpected_reply(reply))
+        }
+    }
+
+    /// Opens a data connection in the current data mode.
+    fn open_data_connection(&mut self) -> Result<PendingData, Error> {
+        // The server's data connections come from the same host as the
+        // control connection. We ignore the address in 'PASV' replies,
+        // which is often wrong for servers behind NAT.
+        let server_ip = self.control.get_ref().peer_addr()?.ip();
+
+        match self.data_mode {
+            DataMode::Passive => {
+                let reply = self.send_command(&protocol::EPSV::Any)?;
+
+                let port = if reply.code == code::ENTERING_PASSIVE_MODE_EXTENDED {
+                    parse_epsv_port(&reply.text.to_string())
+                } else if is_unsupported(&reply.code) && server_ip.is_ipv4() {
+                    let reply = self.expect(&protocol::PASV, &[code::ENTERING_PASSIVE_MODE])?;
+                    parse_pasv_address(&reply.text.to_string()).map(|addr| addr.port())
+                } else {
+                    return Err(unexpected_reply(reply));
+                };
+
+                let port = match port {
+                    Some(port) => port,
+                    None => return Err(ErrorKind::Protocol(protocol::ErrorKind::InvalidReply(
+                        "passive mode reply does not contain a port".to_owned())).into()),
+                };
+
+                let stream = TcpStream::connect_timeout(&SocketAddr::new(server_ip, port), self.data_timeout)?;
+                Ok(PendingData::Connected(stream))
+            },
+            DataMode::Active => {
+                // We listen on the interface we reach the server through.
+                let local_ip = self.control.get_ref().local_addr()?.ip();
+                let listener = TcpListener::bind(SocketAddr::new(local_ip, 0))?;
+                let addr = listener.local_addr()?;
+
+                match local_ip {
+                    IpAddr::V4(ip) => {
+                        self.expect(&protocol::PORT { host_address: ip.octets(), port: addr.port() },
+                                    &[code::OK])?;
+                    },
+                    IpAddr::V6(..) => {
+                        self.expect(&protocol::EPRT::new(addr), &[code::OK])?;
+                    },
+                }
+
+                Ok(PendingData::Listening(listener))
+            },
+        }
+    }
+}
+
+/// Gets the line that is sent for a command.
+///
+/// Fails if an argument can't be sent, so that a path can never
+/// sneak another command onto the control connection.
+fn command_line<C>(command: &C) -> Result<Vec<u8>, Error>
+    where C: Command {
+    let mut line = command.bytes()?;
+    line.extend_from_slice(b"\r\n");
+
+    Ok(line)
+}
+
+/// Waits for the server to connect to us, giving up after a timeout.
+///
+/// Returns `None` if the server sends a reply instead, such as
+/// when it could not connect to us.
+fn accept(listener: &TcpListener, control: &BufReader<TcpStream>, timeout: Duration)
+    -> io::Result<Option<(TcpStream, SocketAddr)>> {
+    listener.set_nonblocking(true)?;
+    let started = Instant::now();
+
+    loop {
+        match listener.accept() {
+            Ok((stream, peer_addr)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(Some((stream, peer_addr)));
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if has_reply(control)? {
+                    return Ok(None);
+                }
+
+                if started.elapsed() >= timeout {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                              "timed out waiting for the server to connect"));
+                }
+
+                thread::sleep(Duration::from_millis(ACCEPT_POLL_INTERVAL_MS));
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Checks if the server has sent anything on the control connection.
+fn has_reply(control: &BufReader<TcpStream>) -> io::Result<bool> {
+    if !control.buffer().is_empty() {
+        return Ok(true);
+    }
+
+    let stream = control.get_ref();
+    stream.set_nonblocking(true)?;
+    let result = stream.peek(&mut [0]);
+    stream.set_nonblocking(false)?;
+
+    match result {
+        // The server closing the connection counts, so reading fails.
+        Ok(..) => Ok(true),
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates the error for a reply we didn't expect.
+fn unexpected_reply(reply: Reply) -> Error {
+    ErrorKind::UnexpectedReply(reply.code.0, reply.text.to_string().trim_end().to_owned()).into()
+}
+
+/// Checks if a reply code means the server doesn't know a command.
+fn is_unsupported(code: &Code) -> bool {
+    *code == code::INVALID_COMMAND || *code == code::SYNTAX_ERROR ||
+        *code == code::COMMAND_NOT_IMPLEMENTED
+}
+
+/// Gets the path out of a reply such as `257 "/foo" is the current directory`.
+///
+/// Quotes inside the path are doubled.
+fn parse_quoted_path(text: &str) -> Option<String> {
+    let start = text.find('"')?;
+    let mut chars = text[start + 1..].chars().peekable();
+    let mut path = String::new();
+
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            if chars.peek() == Some(&'"') {
+                chars.next();
+            } else {
+                return Some(path);
+            }
+        }
+        path.push(c);
+    }
+
+    None
+}
+
+/// Gets the port out of an 'EPSV' reply such as `(|||6446|)`.
+fn parse_epsv_port(text: &str) -> Option<u16> {
+    let start = text.find('(')?;
+    let end = start + text[start..].find(')')?;
+
+    // The server can pick any character as the delimiter.
+    let fields = &text[start + 1..end];
+    let delimiter = fields.chars().next()?;
+    let fields: Vec<&str> = fields.split(delimiter).collect();
+
+    if fields.len() == 5 { fields[3].parse().ok() } else { None }
+}
+
+/// Gets the address out of a 'PASV' reply such as `(127,0,0,1,4,1)`.
+fn parse_pasv_address(text: &str) -> Option<SocketAddr> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let numbers: Vec<&str> = text[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != ',').next()?
+        .split(',').collect();
+
+    if numbers.len() != 6 {
+        return None;
+    }
+
+    let bytes: Result<Vec<u8>, _> = numbers.iter().map(|n| n.parse()).collect();
+    let bytes = bytes.ok()?;
+
+    let ip = IpAddr::from([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    Some(SocketAddr::new(ip, (bytes[4] as u16) << 8 | bytes[5] as u16))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use fs::{self, FileSystem};
+    use server;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use std::{thread, net};
+
+    struct TestServer
+    {
+        file_system: fs::Memory,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl server::Server for TestServer
+    {
+        fn welcome_message(&self) -> String { "test server".to_owned() }
+
+        fn file_system(&self) -> &FileSystem { &self.file_system }
+        fn file_system_mut(&mut self) -> &mut FileSystem { &mut self.file_system }
+
+        fn is_stopped(&self) -> bool { self.stopped.load(Ordering::SeqCst) }
+    }
+
+    /// Stops a test server once the test is done with it.
+    struct RunningServer
+    {
+        stopped: Arc<AtomicBool>,
+        thread: Option<thread::JoinHandle<()>>,
+    }
+
+    impl Drop for RunningServer
+    {
+        fn drop(&mut self) {
+            self.stopped.store(true, Ordering::SeqCst);
+
+            if let Some(thread) = self.thread.take() {
+                thread.join().ok();
+            }
+        }
+    }
+
+    /// Starts a server in the background and logs in to it.
+    fn connect() -> (FtpClient, RunningServer) {
+        let mut file_system = fs::Memory::new();
+        file_system.create_dir(Path::new("/docs")).unwrap();
+        file_system.write_file(Path::new("/docs/hello.txt"), b"hello world".to_vec()).unwrap();
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut server = TestServer { file_system: file_system, stopped: stopped.clone() };
+
+        // Clients can connect as soon as the listener is bound.
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let thread = thread::spawn(move || {
+            server::run_with_listener(&mut server, listener, server::ListenerMode::Plain).unwrap();
+        });
+        let running_server = RunningServer { stopped: stopped, thread: Some(thread) };
+
+        let mut client = FtpClient::connect(address).unwrap();
+        client.login("bob", "secret").unwrap();
+        client.set_file_type(FileType::Binary).unwrap();
+
+        (client, running_server)
+    }
+
+    fn retrieve(client: &mut FtpClient, remote_filename: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        client.retrieve(remote_filename, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn changes_directories() {
+        let (mut client, _server) = connect();
+        assert_eq!(client.pwd().unwrap(), "/");
+
+        client.cwd("docs").unwrap();
+        assert_eq!(client.pwd().unwrap(), "/docs");
+        assert!(client.cwd("missing").is_err());
+
+        client.cdup().unwrap();
+        assert_eq!(client.pwd().unwrap(), "/");
+        client.quit().unwrap();
+    }
+
+    #[test]
+    fn lists_directories() {
+        let (mut client, _server) = connect();
+
+        assert_eq!(client.nlst(Some("docs")).unwrap(), vec!["docs/hello.txt".to_owned()]);
+
+        let listing = client.list(Some("docs")).unwrap();
+        assert_eq!(listing.len(), 1);
+        assert!(listing[0].ends_with("hello.txt"));
+
+        // The listing also has an entry for the directory itself.
+        let entries = client.mlsd(Some("docs")).unwrap();
+        let file = entries.iter().find(|entry| entry.name == "hello.txt").unwrap();
+        assert_eq!(file.facts.size, Some(11));
+    }
+
+    #[test]
+    fn transfers_files_in_passive_mode() {
+        let (mut client, _server) = connect();
+
+        client.store("upload.bin", &mut &b"some data"[..]).unwrap();
+        assert_eq!(retrieve(&mut client, "upload.bin"), b"some data");
+        assert_eq!(retrieve(&mut client, "docs/hello.txt"), b"hello world");
+    }
+
+    #[test]
+    fn transfers_files_in_active_mode() {
+        let (mut client, _server) = connect();
+        client.set_data_mode(DataMode::Active);
+
+        client.store("upload.bin", &mut &b"some data"[..]).unwrap();
+        assert_eq!(retrieve(&mut client, "upload.bin"), b"some data");
+        assert_eq!(client.nlst(None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn resumes_transfers() {
+        let (mut client, _server) = connect();
+
+        client.restart(6);
+        assert_eq!(retrieve(&mut client, "docs/hello.txt"), b"world");
+
+        client.restart(6);
+        client.store("docs/hello.txt", &mut &b"there"[..]).unwrap();
+        assert_eq!(retrieve(&mut client, "docs/hello.txt"), b"hello there");
+    }
+
+    #[test]
+    fn aborted_uploads_leave_existing_files_alone() {
+        let (mut client, _server) = connect();
+
+        let mut stream = client.start_transfer(&protocol::STOR { remote_filename: "docs/hello.txt".to_owned() },
+                                               None).unwrap();
+        stream.write_all(b"goodbye").unwrap();
+        // Give the server time to write what we sent.
+        thread::sleep(Duration::from_millis(100));
+
+        assert_eq!(client.send_command(&protocol::ABOR).unwrap().code, code::CONNECTION_CLOSED_TRANSFER_ABORTED);
+        assert_eq!(client.read_reply().unwrap().code, code::CLOSING_DATA_CONNECTION);
+        drop(stream);
+
+        assert_eq!(retrieve(&mut client, "docs/hello.txt"), b"hello world");
+        assert_eq!(client.list(Some("-a docs")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn renames_and_deletes_files() {
+        let (mut client, _server) = connect();
+
+        client.rename("docs/hello.txt", "docs/greeting.txt").unwrap();
+        assert_eq!(client.nlst(Some("docs")).unwrap(), vec!["docs/greeting.txt".to_owned()]);
+
+        client.delete("docs/greeting.txt").unwrap();
+        assert!(client.nlst(Some("docs")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_unexpected_replies() {
+        let (mut client, _server) = connect();
+
+        match client.delete("missing.txt") {
+            Err(Error(ErrorKind::UnexpectedReply(550, _), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // The connection is still usable afterwards.
+        assert_eq!(client.pwd().unwrap(), "/");
+    }
+
+    #[test]
+    fn refuses_to_send_line_breaks_in_paths() {
+        let (mut client, _server) = connect();
+
+        assert!(client.delete("missing.txt\r\nDELE docs/hello.txt").is_err());
+        assert!(client.store("new.txt\nDELE docs/hello.txt", &mut &b"data"[..]).is_err());
+
+        // Nothing was sent, so the file is still there.
+        assert_eq!(retrieve(&mut client, "docs/hello.txt"), b"hello world");
+    }
+
+    #[test]
+    fn sends_paths_as_utf8() {
+        let (mut client, _server) = connect();
+
+        client.store("caf\u{e9}.txt", &mut &b"data"[..]).unwrap();
+        assert_eq!(retrieve(&mut client, "caf\u{e9}.txt"), b"data");
+    }
+
+    #[test]
+    fn gives_up_waiting_for_active_connections() {
+        // A server that agrees to everything, but never connects to us.
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+
+            stream.write_all(b"220 ready\r\n").unwrap();
+            while let Some(Ok(line)) = lines.next() {
+                let reply: &[u8] = if line.starts_with("PORT") { b"200 ok\r\n" } else { b"150 ok\r\n" };
+                stream.write_all(reply).ok();
+            }
+        });
+
+        let mut client = FtpClient::connect(address).unwrap();
+        client.set_data_mode(DataMode::Active);
+        client.set_data_timeout(Duration::from_millis(100));
+
+        match client.nlst(None) {
+            Err(Error(ErrorKind::Io(ref e), _)) if e.kind() == io::ErrorKind::TimedOut => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn stops_waiting_for_active_connections_the_server_gave_up_on() {
+        // A server that can never connect to us.
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+
+            stream.write_all(b"220 ready\r\n").unwrap();
+            while let Some(Ok(line)) = lines.next() {
+                let reply: &[u8] = if line.starts_with("PORT") {
+                    b"200 ok\r\n"
+                } else {
+                    b"150 ok\r\n425 could not connect\r\n"
+                };
+                stream.write_all(reply).ok();
+            }
+        });
+
+        let mut client = FtpClient::connect(address).unwrap();
+        client.set_data_mode(DataMode::Active);
+
+        // Long enough that we'd notice waiting for it.
+        client.set_data_timeout(Duration::from_secs(30));
+        let started = Instant::now();
+
+        match client.nlst(None) {
+            Err(Error(ErrorKind::UnexpectedReply(425, _), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn correctly_parses_quoted_paths() {
+        assert_eq!(parse_quoted_path("\"/foo\" is the current directory"), Some("/foo".to_owned()));
+        assert_eq!(parse_quoted_path("\"/a \"\"quoted\"\" name\""), Some("/a \"quoted\" name".to_owned()));
+        assert_eq!(parse_quoted_path("no path"), None);
+        assert_eq!(parse_quoted_path("\"/unterminated"), None);
+    }
+
+    #[test]
+    fn correctly_parses_passive_replies() {
+        assert_eq!(parse_epsv_port("Entering Extended Passive Mode (|||6446|)"), Some(6446));
+        assert_eq!(parse_epsv_port("passive mode enabled (!!!21!)"), Some(21));
+        assert_eq!(parse_epsv_port("no port"), None);
+
+        assert_eq!(parse_pasv_address("Entering Passive Mode (127,0,0,1,4,1)"),
+                   Some("127.0.0.1:1025".parse().unwrap()));
+        assert_eq!(parse_pasv_address("Entering Passive Mode 10,0,0,2,0,21"),
+                   Some("10.0.0.2:21".parse().unwrap()));
+        assert_eq!(parse_pasv_address("Entering Passive Mode (127,0,0,1)"), None);
+    }
+}
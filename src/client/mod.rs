@@ -0,0 +1,5 @@
+//! Utilities for talking to FTP servers.
+
+pub use self::ftp_client::{FtpClient, DataMode};
+
+mod ftp_client;
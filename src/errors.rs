@@ -12,5 +12,17 @@ error_chain! {
     foreign_links {
         Io(::std::io::Error);
     }
+
+    errors {
+        InvalidTlsConfiguration(reason: String) {
+            description("invalid TLS configuration")
+            display("invalid TLS configuration: {}", reason)
+        }
+
+        UnexpectedReply(code: u16, text: String) {
+            description("received unexpected reply")
+            display("received unexpected reply: {} {}", code, text)
+        }
+    }
 }
 
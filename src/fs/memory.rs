@@ -1,11 +1,18 @@
 use Error;
-use super::FileSystem;
+use super::{FileSystem, Metadata, DirEntry, FileKind, WriteMode};
 
 use std::collections::HashMap;
-use std::path::Path;
+use std::io::prelude::*;
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::{cmp, io};
 
 const ROOT_DIR_NAME: &'static str = "";
 
+/// The user and group that own everything in the filesystem.
+const OWNER_NAME: &'static str = "ftp";
+
 /// An in-memory filesystem.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Memory
@@ -28,16 +35,41 @@ enum NodeKind
     Directory(Directory),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// A file.
+///
+/// The contents are shared with any readers and writers that
+/// have the file open.
+#[derive(Debug)]
 struct File
+{
+    contents: Arc<Mutex<Contents>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Contents
 {
     data: Vec<u8>,
+    modified: SystemTime,
+}
+
+/// Reads from a file in a `Memory` file system.
+struct FileReader
+{
+    contents: Arc<Mutex<Contents>>,
+    position: u64,
+}
+
+/// Writes to the end of a file in a `Memory` file system.
+struct FileWriter
+{
+    contents: Arc<Mutex<Contents>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct Directory
 {
     nodes: HashMap<String, Node>,
+    modified: SystemTime,
 }
 
 impl Memory
@@ -47,7 +79,7 @@ impl Memory
         Memory {
             root: Node {
                 name: ROOT_DIR_NAME.to_owned(),
-                kind: NodeKind::Directory(Directory { nodes: HashMap::new() })
+                kind: NodeKind::Directory(Directory::new()),
             },
         }
     }
@@ -57,99 +89,142 @@ impl Memory
         if let NodeKind::Directory(ref mut dir) = self.root.kind { dir } else { unreachable!() }
     }
 
-    fn find_parent_and_name(&self, path: &Path) -> Result<(&Node, String), Error> {
-        if let Some(parent) = path.parent() {
-            let file_name = path.file_name().unwrap().to_str().unwrap().to_owned();
-            Ok((self.find_node(parent)?, file_name))
-        } else {
-            // FIXME: better error handling.
-            panic!("no parent");
-        }
-    }
-
     fn find_parent_and_name_mut(&mut self, path: &Path) -> Result<(&mut Node, String), Error> {
-        if let Some(parent) = path.parent() {
-            let file_name = path.file_name().unwrap().to_str().unwrap().to_owned();
-            Ok((self.find_node_mut(parent)?, file_name))
-        } else {
-            // FIXME: better error handling.
-            panic!("no parent");
-        }
+        let (parent, file_name) = split_parent_and_name(path)?;
+        Ok((self.find_node_mut(parent)?, file_name))
     }
 
-    fn find_node(&self, path: &Path) -> Result<&Node, Error> {
-        let mut parts: Vec<_> = path.iter().map(|s| s.to_str().unwrap()).collect();
+    fn find_parent_dir_mut(&mut self, path: &Path) -> Result<(&mut Directory, String), Error> {
+        let (parent, file_name) = self.find_parent_and_name_mut(path)?;
 
-        // Skip the '/' if it exists.
-        if parts.first() == Some(&"/") {
-            parts = parts[1..].to_owned();
+        match parent.kind {
+            NodeKind::Directory(ref mut dir) => Ok((dir, file_name)),
+            NodeKind::File(..) => Err(not_a_directory(path.parent().unwrap())),
         }
+    }
 
-        if parts.is_empty() {
-            Ok(&self.root)
-        } else {
-            let mut the_parts = vec![ROOT_DIR_NAME];
-            the_parts.extend(parts);
-            let node = self.root.find_node(the_parts);
+    fn find_node(&self, path: &Path) -> Result<&Node, Error> {
+        let parts = path_parts(path)?;
 
-            if let Some(node) = node { Ok(node) } else { panic!("path does not exist") }
+        match self.root.find_node(&parts) {
+            Some(node) => Ok(node),
+            None => Err(not_found(path)),
         }
     }
 
     fn find_node_mut(&mut self, path: &Path) -> Result<&mut Node, Error> {
-        let mut parts: Vec<_> = path.iter().map(|s| s.to_str().unwrap()).collect();
-
-        // Skip the '/' if it exists.
-        if parts.first() == Some(&"/") {
-            parts = parts[1..].to_owned();
-        }
-
-        if parts.is_empty() {
-            Ok(&mut self.root)
-        } else {
-            let mut the_parts = vec![ROOT_DIR_NAME];
-            the_parts.extend(parts);
-            let node = self.root.find_node_mut(the_parts);
+        let parts = path_parts(path)?;
 
-            if let Some(node) = node { Ok(node) } else { panic!("path does not exist") }
+        match self.root.find_node_mut(&parts) {
+            Some(node) => Ok(node),
+            None => Err(not_found(path)),
         }
     }
 }
 
 impl Node
 {
-    fn find_node(&self, parts: Vec<&str>) -> Option<&Self> {
-        if parts == vec![&self.name] { return Some(self) };
+    fn metadata(&self) -> Metadata {
+        let (kind, size, modified, permissions) = match self.kind {
+            NodeKind::File(ref file) => {
+                let contents = file.contents.lock().unwrap();
+                (FileKind::File, contents.data.len() as u64, contents.modified, 0o644)
+            },
+            NodeKind::Directory(ref dir) => (FileKind::Directory, 0, dir.modified, 0o755),
+        };
+
+        Metadata {
+            kind: kind,
+            size: size,
+            modified: modified,
+            permissions: permissions,
+            owner: OWNER_NAME.to_owned(),
+            group: OWNER_NAME.to_owned(),
+            symlink_target: None,
+        }
+    }
+
+    #[cfg(test)]
+    fn without_modified_times(mut self) -> Self {
+        match self.kind {
+            NodeKind::File(ref mut file) => file.contents.lock().unwrap().modified = SystemTime::UNIX_EPOCH,
+            NodeKind::Directory(ref mut dir) => {
+                dir.modified = SystemTime::UNIX_EPOCH;
+                dir.nodes = dir.nodes.drain().map(|(name, node)| (name, node.without_modified_times())).collect();
+            },
+        }
+        self
+    }
 
-        if let NodeKind::Directory(ref dir) = self.kind {
-            let child_parts = parts[1..].to_owned();
+    fn find_node(&self, parts: &[&str]) -> Option<&Self> {
+        match parts.split_first() {
+            Some((first, rest)) if *first == self.name => {
+                if rest.is_empty() { return Some(self) };
 
-            for node in dir.nodes.values() { if let Some(node) = node.find_node(child_parts.clone()) {
-                return Some(node)
-            }}
+                if let NodeKind::Directory(ref dir) = self.kind {
+                    dir.nodes.get(rest[0]).and_then(|node| node.find_node(rest))
+                } else {
+                    None
+                }
+            },
+            _ => None,
         }
-        None
     }
 
-    fn find_node_mut(&mut self, parts: Vec<&str>) -> Option<&mut Self> {
-        if parts == vec![&self.name] { return Some(self) };
+    fn find_node_mut(&mut self, parts: &[&str]) -> Option<&mut Self> {
+        match parts.split_first() {
+            Some((first, rest)) if *first == self.name => {
+                if rest.is_empty() { return Some(self) };
 
-        if let NodeKind::Directory(ref mut dir) = self.kind {
-            let child_parts = parts[1..].to_owned();
+                if let NodeKind::Directory(ref mut dir) = self.kind {
+                    dir.nodes.get_mut(rest[0]).and_then(|node| node.find_node_mut(rest))
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        }
+    }
+}
 
-            for node in dir.nodes.values_mut() { if let Some(node) = node.find_node_mut(child_parts.clone()) {
-                return Some(node)
-            }}
+impl File
+{
+    pub fn new(data: Vec<u8>) -> Self {
+        File {
+            contents: Arc::new(Mutex::new(Contents { data: data, modified: SystemTime::now() })),
         }
-        None
     }
 }
 
+impl Clone for File
+{
+    /// Copies the contents, so that writing to the copy leaves
+    /// the original alone.
+    fn clone(&self) -> Self {
+        File { contents: Arc::new(Mutex::new(self.contents.lock().unwrap().clone())) }
+    }
+}
+
+impl PartialEq for File
+{
+    fn eq(&self, other: &Self) -> bool {
+        // Locking the same file twice would deadlock.
+        Arc::ptr_eq(&self.contents, &other.contents) ||
+            *self.contents.lock().unwrap() == *other.contents.lock().unwrap()
+    }
+}
+
+impl Eq for File { }
+
 impl Directory
 {
-    #[cfg(test)]
     pub fn new() -> Self {
-        Directory { nodes: HashMap::new() }
+        Directory { nodes: HashMap::new(), modified: SystemTime::now() }
+    }
+
+    /// Marks the directory as modified after its entries have changed.
+    fn touch(&mut self) {
+        self.modified = SystemTime::now();
     }
 
     #[cfg(test)]
@@ -159,6 +234,31 @@ impl Directory
     }
 }
 
+impl Read for FileReader
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let contents = self.contents.lock().unwrap();
+        let start = cmp::min(self.position, contents.data.len() as u64) as usize;
+
+        let bytes_read = (&contents.data[start..]).read(buf)?;
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl Write for FileWriter
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut contents = self.contents.lock().unwrap();
+
+        contents.data.extend_from_slice(buf);
+        contents.modified = SystemTime::now();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
 impl FileSystem for Memory
 {
     fn list(&self, path: &Path)
@@ -169,64 +269,223 @@ impl FileSystem for Memory
             NodeKind::Directory(ref dir) => {
                 Ok(dir.nodes.values().map(|node| node.name.clone()).collect())
             },
-            // FIXME: better error handling
-            NodeKind::File(..) => panic!("this is not a directory"),
+            NodeKind::File(..) => Err(not_a_directory(path)),
         }
     }
 
+    fn list_entries(&self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let parent_node = self.find_node(path)?;
+
+        match parent_node.kind {
+            NodeKind::Directory(ref dir) => {
+                Ok(dir.nodes.values().map(|node| DirEntry {
+                    name: node.name.clone(),
+                    metadata: node.metadata(),
+                }).collect())
+            },
+            NodeKind::File(..) => Err(not_a_directory(path)),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        Ok(self.find_node(path)?.metadata())
+    }
+
     fn create_dir(&mut self, path: &Path) -> Result<(), Error> {
         let (parent, file_name) = self.find_parent_and_name_mut(path)?;
 
         if let NodeKind::Directory(ref mut dir) = parent.kind {
+            if dir.nodes.contains_key(&file_name) {
+                return Err(already_exists(path));
+            }
+
             dir.nodes.insert(file_name.clone(), Node {
                 name: file_name,
-                kind: NodeKind::Directory(Directory { nodes: HashMap::new() }),
+                kind: NodeKind::Directory(Directory::new()),
             });
+            dir.touch();
             Ok(())
         } else {
-            // FIXME: better error handling
-            panic!("not a dir")
+            Err(not_a_directory(path.parent().unwrap()))
         }
     }
 
-    fn write_file(&mut self, path: &Path, data: Vec<u8>) -> Result<(), Error> {
-        let (parent, file_name) = self.find_parent_and_name_mut(path)?;
+    fn open_reader(&self, path: &Path, offset: u64) -> Result<Box<Read>, Error> {
+        match self.find_node(path)?.kind {
+            NodeKind::File(ref file) => Ok(Box::new(FileReader {
+                contents: file.contents.clone(),
+                position: offset,
+            })),
+            NodeKind::Directory(..) => Err(is_a_directory(path)),
+        }
+    }
 
-        if let NodeKind::Directory(ref mut dir) = parent.kind {
+    fn open_writer(&mut self, path: &Path, mode: WriteMode) -> Result<Box<Write>, Error> {
+        let (dir, file_name) = self.find_parent_dir_mut(path)?;
+
+        let replace = match (mode, dir.nodes.get(&file_name)) {
+            (_, Some(&Node { kind: NodeKind::Directory(..), .. })) => return Err(is_a_directory(path)),
+            (WriteMode::Offset(..), None) => return Err(not_found(path)),
+            (WriteMode::CreateNew, Some(..)) => return Err(already_exists(path)),
+            (WriteMode::Truncate, _) | (WriteMode::CreateNew, None) | (WriteMode::Append, None) => true,
+            _ => false,
+        };
+
+        if replace {
             dir.nodes.insert(file_name.clone(), Node {
-                name: file_name,
-                kind: NodeKind::File(File { data: data }),
+                name: file_name.clone(),
+                kind: NodeKind::File(File::new(Vec::new())),
             });
-            Ok(())
-        } else {
-            // FIXME: better error handling
-            panic!("parent must be a directory");
+            dir.touch();
         }
-    }
 
-    fn read_file(&self, path: &Path) -> Result<Vec<u8>, Error> {
-        let (parent, file_name) = self.find_parent_and_name(path)?;
+        match dir.nodes[&file_name].kind {
+            NodeKind::File(ref file) => {
+                if let WriteMode::Offset(offset) = mode {
+                    let mut contents = file.contents.lock().unwrap();
 
-        if let NodeKind::Directory(ref dir) = parent.kind {
-            if let Some(ref node) = dir.nodes.get(&file_name) {
-                match node.kind {
-                    NodeKind::File(ref file) => {
-                        Ok(file.data.clone())
-                    },
-                    NodeKind::Directory(..) => {
-                        // FIXME: better error handling
-                        panic!("cannot read a directory");
-                    },
+                    if offset > contents.data.len() as u64 {
+                        return Err(offset_past_end(path));
+                    }
+                    contents.data.truncate(offset as usize);
                 }
-            } else {
-                // FIXME: better error handling
-                panic!("file does not exist");
+
+                Ok(Box::new(FileWriter { contents: file.contents.clone() }))
+            },
+            NodeKind::Directory(..) => unreachable!(),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Result<(), Error> {
+        let (dir, file_name) = self.find_parent_dir_mut(path)?;
+
+        match dir.nodes.get(&file_name).map(|node| &node.kind) {
+            Some(&NodeKind::File(..)) => (),
+            Some(&NodeKind::Directory(..)) => return Err(is_a_directory(path)),
+            None => return Err(not_found(path)),
+        }
+
+        dir.nodes.remove(&file_name);
+        dir.touch();
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> Result<(), Error> {
+        let (dir, file_name) = self.find_parent_dir_mut(path)?;
+
+        match dir.nodes.get(&file_name).map(|node| &node.kind) {
+            Some(&NodeKind::Directory(ref child)) => if !child.nodes.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::DirectoryNotEmpty,
+                    format!("directory is not empty: {}", path.display())).into());
+            },
+            Some(&NodeKind::File(..)) => return Err(not_a_directory(path)),
+            None => return Err(not_found(path)),
+        }
+
+        dir.nodes.remove(&file_name);
+        dir.touch();
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        if from == to {
+            return self.find_node(from).map(|_| ());
+        }
+
+        if to.starts_with(from) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("cannot move {} into itself", from.display())).into());
+        }
+
+        let mut node = {
+            let (dir, file_name) = self.find_parent_dir_mut(from)?;
+
+            match dir.nodes.remove(&file_name) {
+                Some(node) => { dir.touch(); node },
+                None => return Err(not_found(from)),
             }
-        } else {
-            // FIXME: better error handling
-            panic!("parent must be a directory");
+        };
+
+        let result = match self.find_parent_dir_mut(to) {
+            Ok((dir, file_name)) => {
+                if let Some(&Node { kind: NodeKind::Directory(..), .. }) = dir.nodes.get(&file_name) {
+                    Err((is_a_directory(to), node))
+                } else {
+                    node.name = file_name.clone();
+                    dir.nodes.insert(file_name, node);
+                    dir.touch();
+                    Ok(())
+                }
+            },
+            Err(e) => Err((e, node)),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err((e, node)) => {
+                // Put the node back where we found it.
+                let (dir, _) = self.find_parent_dir_mut(from)?;
+                dir.nodes.insert(node.name.clone(), node);
+                Err(e)
+            },
+        }
+    }
+}
+
+/// Splits a path up into the node names leading to it, starting with the root.
+fn path_parts(path: &Path) -> Result<Vec<&str>, Error> {
+    let mut parts = vec![ROOT_DIR_NAME];
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => match part.to_str() {
+                Some(part) => parts.push(part),
+                None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("path is not valid UTF-8: {}", path.display())).into()),
+            },
+            Component::RootDir | Component::CurDir => (),
+            Component::ParentDir | Component::Prefix(..) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("path must be absolute and normalized: {}", path.display())).into());
+            },
         }
     }
+
+    Ok(parts)
+}
+
+/// Splits a path into its parent directory and its file name.
+fn split_parent_and_name(path: &Path) -> Result<(&Path, String), Error> {
+    match (path.parent(), path.file_name().and_then(|name| name.to_str())) {
+        (Some(parent), Some(file_name)) => Ok((parent, file_name.to_owned())),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("path does not name a file: {}", path.display())).into()),
+    }
+}
+
+fn not_found(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::NotFound,
+                   format!("path does not exist: {}", path.display())).into()
+}
+
+fn not_a_directory(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::NotADirectory,
+                   format!("not a directory: {}", path.display())).into()
+}
+
+fn already_exists(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::AlreadyExists,
+                   format!("path already exists: {}", path.display())).into()
+}
+
+fn offset_past_end(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::InvalidInput,
+                   format!("offset is past the end of the file: {}", path.display())).into()
+}
+
+fn is_a_directory(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::IsADirectory,
+                   format!("is a directory: {}", path.display())).into()
 }
 
 #[cfg(test)]
@@ -234,6 +493,12 @@ mod test
 {
     pub use super::*;
 
+    #[test]
+    fn can_be_sent_between_threads() {
+        fn assert_send<T: Send>() { }
+        assert_send::<Memory>();
+    }
+
     mod find_node {
         use super::super::{Node, NodeKind, File, Directory};
         pub use super::*;
@@ -256,7 +521,7 @@ mod test
             let mut fs = Memory::new();
             let foo = Node {
                 name: "foo".to_owned(),
-                kind: NodeKind::File(File { data: vec![1,2,3] }),
+                kind: NodeKind::File(File::new(vec![1,2,3])),
             };
 
             fs.root_dir_mut().nodes.insert(foo.name.clone(), foo.clone());
@@ -294,6 +559,40 @@ mod test
 
             assert_eq!(fs.find_node(&Path::new("/bar/foo")).unwrap(), &foo);
         }
+
+        #[test]
+        fn does_not_find_nested_file_in_wrong_directory() {
+            let mut fs = Memory::new();
+            let foo = Node {
+                name: "foo".to_owned(),
+                kind: NodeKind::File(File::new(vec![1,2,3])),
+            };
+
+            let bar = Node {
+                name: "bar".to_owned(),
+                kind: NodeKind::Directory(Directory::new().add(foo.clone())),
+            };
+
+            fs.root_dir_mut().nodes.insert(bar.name.clone(), bar.clone());
+
+            assert!(fs.find_node(&Path::new("/baz/foo")).is_err());
+        }
+
+        #[test]
+        fn fails_to_find_missing_nested_file() {
+            let mut fs = Memory::new();
+            let bar = Node {
+                name: "bar".to_owned(),
+                kind: NodeKind::Directory(Directory::new().add(Node {
+                    name: "baz".to_owned(),
+                    kind: NodeKind::Directory(Directory::new()),
+                })),
+            };
+
+            fs.root_dir_mut().nodes.insert(bar.name.clone(), bar.clone());
+
+            assert!(fs.find_node(&Path::new("/foo")).is_err());
+        }
     }
 
     mod create_dir {
@@ -306,13 +605,13 @@ mod test
             let mut fs = Memory::new();
             fs.create_dir(&Path::new("/bar")).unwrap();
 
-            assert_eq!(fs.root, Node {
+            assert_eq!(fs.root.without_modified_times(), Node {
                 name: super::super::ROOT_DIR_NAME.to_owned(),
                 kind: NodeKind::Directory(Directory::new().add(Node {
                     name: "bar".to_owned(),
                     kind: NodeKind::Directory(Directory::new()),
                 })),
-            });
+            }.without_modified_times());
         }
     }
 
@@ -327,13 +626,273 @@ mod test
 
             fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
 
-            assert_eq!(fs.root, Node {
+            assert_eq!(fs.root.without_modified_times(), Node {
                 name: super::super::ROOT_DIR_NAME.to_owned(),
                 kind: NodeKind::Directory(Directory::new().add(Node {
                     name: "foo.txt".to_owned(),
-                    kind: NodeKind::File(File { data: vec![1,2,3] }),
+                    kind: NodeKind::File(File::new(vec![1,2,3])),
                 })),
-            });
+            }.without_modified_times());
+        }
+
+        #[test]
+        fn fails_to_create_a_file_in_a_missing_directory() {
+            let mut fs = Memory::new();
+            assert!(fs.write_file(&Path::new("/foo/bar.txt"), vec![1,2,3]).is_err());
+        }
+
+        #[test]
+        fn fails_to_create_an_existing_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            fs.write_file(&Path::new("/foo/bar.txt"), vec![1,2,3]).unwrap();
+
+            assert!(fs.create_dir(&Path::new("/foo")).is_err());
+            assert_eq!(fs.read_file(&Path::new("/foo/bar.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn fails_to_overwrite_a_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            assert!(fs.write_file(&Path::new("/foo"), vec![1,2,3]).is_err());
+        }
+    }
+
+    mod append_file {
+        pub use super::*;
+        use super::super::FileSystem;
+        use std::path::Path;
+
+        #[test]
+        fn correctly_creates_a_missing_file() {
+            let mut fs = Memory::new();
+
+            fs.append_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn correctly_appends_to_an_existing_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            fs.append_file(&Path::new("/foo.txt"), vec![4,5]).unwrap();
+            assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3,4,5]);
+        }
+
+        #[test]
+        fn fails_to_append_to_a_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            assert!(fs.append_file(&Path::new("/foo"), vec![1,2,3]).is_err());
+        }
+    }
+
+    mod open_writer {
+        pub use super::*;
+        use super::super::{FileSystem, WriteMode};
+        use std::path::Path;
+
+        #[test]
+        fn correctly_replaces_the_end_of_a_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3,4]).unwrap();
+            fs.open_writer(&Path::new("/foo.txt"), WriteMode::Offset(2)).unwrap().write_all(&[5]).unwrap();
+            assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,5]);
+        }
+
+        #[test]
+        fn fails_to_write_past_the_end_of_a_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            assert!(fs.open_writer(&Path::new("/foo.txt"), WriteMode::Offset(4)).is_err());
+            assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn fails_to_write_to_a_missing_file_at_an_offset() {
+            let mut fs = Memory::new();
+            assert!(fs.open_writer(&Path::new("/foo.txt"), WriteMode::Offset(0)).is_err());
+        }
+
+        #[test]
+        fn fails_to_create_a_new_file_over_an_existing_one() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            assert!(fs.open_writer(&Path::new("/foo.txt"), WriteMode::CreateNew).is_err());
+            assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn writes_are_visible_before_the_writer_is_closed() {
+            let mut fs = Memory::new();
+
+            let mut writer = fs.open_writer(&Path::new("/foo.txt"), WriteMode::Truncate).unwrap();
+            writer.write_all(&[1,2]).unwrap();
+            writer.write_all(&[3]).unwrap();
+
+            assert_eq!(fs.metadata(&Path::new("/foo.txt")).unwrap().size, 3);
+        }
+    }
+
+    mod remove_file {
+        pub use super::*;
+        use super::super::FileSystem;
+        use std::path::Path;
+
+        #[test]
+        fn correctly_removes_a_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            fs.remove_file(&Path::new("/foo.txt")).unwrap();
+            assert_eq!(fs.root.without_modified_times(), Memory::new().root.without_modified_times());
+        }
+
+        #[test]
+        fn fails_to_remove_a_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            assert!(fs.remove_file(&Path::new("/foo")).is_err());
+        }
+    }
+
+    mod remove_dir {
+        pub use super::*;
+        use super::super::FileSystem;
+        use std::path::Path;
+
+        #[test]
+        fn correctly_removes_an_empty_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            fs.remove_dir(&Path::new("/foo")).unwrap();
+            assert_eq!(fs.root.without_modified_times(), Memory::new().root.without_modified_times());
+        }
+
+        #[test]
+        fn fails_to_remove_a_non_empty_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            fs.write_file(&Path::new("/foo/bar.txt"), vec![1,2,3]).unwrap();
+            assert!(fs.remove_dir(&Path::new("/foo")).is_err());
+        }
+
+        #[test]
+        fn fails_to_remove_a_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            assert!(fs.remove_dir(&Path::new("/foo.txt")).is_err());
+        }
+    }
+
+    mod rename {
+        pub use super::*;
+        use super::super::FileSystem;
+        use std::path::Path;
+
+        #[test]
+        fn correctly_renames_a_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            fs.rename(&Path::new("/foo.txt"), &Path::new("/bar.txt")).unwrap();
+
+            assert!(fs.read_file(&Path::new("/foo.txt")).is_err());
+            assert_eq!(fs.read_file(&Path::new("/bar.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn correctly_moves_a_file_into_a_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/dir")).unwrap();
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            fs.rename(&Path::new("/foo.txt"), &Path::new("/dir/foo.txt")).unwrap();
+
+            assert_eq!(fs.read_file(&Path::new("/dir/foo.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn leaves_the_file_alone_when_the_destination_is_missing() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            assert!(fs.rename(&Path::new("/foo.txt"), &Path::new("/dir/foo.txt")).is_err());
+
+            assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3]);
+        }
+
+        #[test]
+        fn fails_to_move_a_directory_into_itself() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/dir")).unwrap();
+            assert!(fs.rename(&Path::new("/dir"), &Path::new("/dir/sub")).is_err());
+        }
+    }
+
+    mod metadata {
+        pub use super::*;
+        use super::super::{FileSystem, FileKind};
+        use std::path::Path;
+
+        #[test]
+        fn correctly_describes_a_file() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            let metadata = fs.metadata(&Path::new("/foo.txt")).unwrap();
+
+            assert_eq!(metadata.kind, FileKind::File);
+            assert_eq!(metadata.size, 3);
+        }
+
+        #[test]
+        fn correctly_describes_a_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            assert_eq!(fs.metadata(&Path::new("/foo")).unwrap().kind, FileKind::Directory);
+        }
+
+        #[test]
+        fn updates_modified_times_on_write() {
+            let mut fs = Memory::new();
+            let before = SystemTime::now();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            let created = fs.metadata(&Path::new("/foo.txt")).unwrap().modified;
+
+            fs.append_file(&Path::new("/foo.txt"), vec![4]).unwrap();
+            let appended = fs.metadata(&Path::new("/foo.txt")).unwrap().modified;
+
+            assert!(appended >= created);
+            // Creating the file changes the directory, but writing to it doesn't.
+            assert!(fs.metadata(&Path::new("/")).unwrap().modified >= before);
+        }
+
+        #[test]
+        fn correctly_lists_entries() {
+            let mut fs = Memory::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            let entries = fs.list_entries(&Path::new("/")).unwrap();
+
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].name, "foo.txt");
+            assert_eq!(entries[0].metadata.size, 3);
         }
     }
 
@@ -349,5 +908,23 @@ mod test
             fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
             assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3]);
         }
+
+        #[test]
+        fn correctly_reads_from_an_offset() {
+            let mut fs = Memory::new();
+            let mut data = Vec::new();
+
+            fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+            fs.open_reader(&Path::new("/foo.txt"), 1).unwrap().read_to_end(&mut data).unwrap();
+            assert_eq!(data, vec![2,3]);
+        }
+
+        #[test]
+        fn fails_to_read_a_directory() {
+            let mut fs = Memory::new();
+
+            fs.create_dir(&Path::new("/foo")).unwrap();
+            assert!(fs.open_reader(&Path::new("/foo"), 0).is_err());
+        }
     }
 }
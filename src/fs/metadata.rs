@@ -0,0 +1,50 @@
+//! Information about files and directories.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// The kind of an entry in a file system.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileKind
+{
+    File,
+    Directory,
+    Symlink,
+}
+
+/// Information about a file or directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata
+{
+    /// What sort of entry this is.
+    pub kind: FileKind,
+    /// The size in bytes.
+    pub size: u64,
+    /// When the entry was last modified.
+    pub modified: SystemTime,
+    /// The Unix permission bits, for example `0o644`.
+    pub permissions: u32,
+    /// The user that owns the entry.
+    pub owner: String,
+    /// The group that owns the entry.
+    pub group: String,
+    /// Where the entry points to if it is a symlink.
+    pub symlink_target: Option<PathBuf>,
+}
+
+/// An entry in a directory listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry
+{
+    /// The name of the entry within its directory.
+    pub name: String,
+    /// Information about the entry.
+    pub metadata: Metadata,
+}
+
+impl Metadata
+{
+    pub fn is_file(&self) -> bool { self.kind == FileKind::File }
+    pub fn is_dir(&self) -> bool { self.kind == FileKind::Directory }
+    pub fn is_symlink(&self) -> bool { self.kind == FileKind::Symlink }
+}
@@ -5,26 +5,98 @@
 
 pub use self::physical::Physical;
 pub use self::memory::Memory;
+pub use self::metadata::{Metadata, DirEntry, FileKind};
 
 mod physical;
 mod memory;
+mod metadata;
 
 use Error;
+use std::io::prelude::*;
 use std::path::Path;
 
+/// How a file is opened for writing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WriteMode
+{
+    /// Create the file, replacing any existing one.
+    Truncate,
+    /// Create the file, failing if it already exists.
+    CreateNew,
+    /// Write to the end of the file, creating it if it does not exist.
+    Append,
+    /// Replace everything after an offset.
+    ///
+    /// Used to resume uploads. Fails if the file is shorter than the offset.
+    Offset(u64),
+}
+
 /// A filesystem mountable as FTP.
 pub trait FileSystem
 {
     /// List all files/directories at a specific path.
     fn list(&self, path: &Path) -> Result<Vec<String>, Error>;
 
+    /// List all files/directories at a specific path, along with
+    /// information about each of them.
+    ///
+    /// Symlinks in the directory are not followed.
+    fn list_entries(&self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let mut entries = Vec::new();
+
+        for name in self.list(path)? {
+            let metadata = self.metadata(&path.join(&name))?;
+            entries.push(DirEntry { name: name, metadata: metadata });
+        }
+
+        Ok(entries)
+    }
+
+    /// Get information about a file or directory.
+    ///
+    /// Symlinks are followed.
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error>;
+
     /// Make a new directory.
     fn create_dir(&mut self, path: &Path) -> Result<(), Error>;
 
+    /// Open a file for reading, starting from an offset.
+    ///
+    /// Reading from an offset past the end of the file gives no data.
+    fn open_reader(&self, path: &Path, offset: u64) -> Result<Box<Read>, Error>;
+
+    /// Open a file for writing.
+    fn open_writer(&mut self, path: &Path, mode: WriteMode) -> Result<Box<Write>, Error>;
+
+    /// Read all of the data in a file.
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+
+        self.open_reader(path, 0)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
     /// Write data into a file.
-    fn write_file(&mut self, path: &Path, data: Vec<u8>) -> Result<(), Error>;
+    fn write_file(&mut self, path: &Path, data: Vec<u8>) -> Result<(), Error> {
+        self.open_writer(path, WriteMode::Truncate)?.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Append data to the end of a file.
+    ///
+    /// The file is created if it does not already exist.
+    fn append_file(&mut self, path: &Path, data: Vec<u8>) -> Result<(), Error> {
+        self.open_writer(path, WriteMode::Append)?.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Delete a file.
+    fn remove_file(&mut self, path: &Path) -> Result<(), Error>;
+
+    /// Delete an empty directory.
+    fn remove_dir(&mut self, path: &Path) -> Result<(), Error>;
 
-    /// Read data from a file.
-    fn read_file(&self, path: &Path) -> Result<Vec<u8>, Error>;
+    /// Move a file or directory to a new path.
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), Error>;
 }
 
@@ -1,10 +1,14 @@
 use Error;
-use super::FileSystem;
+use super::{FileSystem, Metadata, DirEntry, FileKind, WriteMode};
 
-use std::path::{Path, PathBuf};
-use std::fs;
+use std::io::prelude::*;
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
 
 /// A folder on the physical on-disk filesystem.
+///
+/// Clients can never access anything outside of the root directory,
+/// whether it be through '..', absolute paths, or symlinks.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Physical
 {
@@ -21,33 +25,464 @@ impl Physical
             root: root.into(),
         }
     }
+
+    /// Gets the on-disk path of a path in the FTP file system.
+    ///
+    /// Fails if the path leads outside of the root directory.
+    fn full_path(&self, path: &Path) -> Result<PathBuf, Error> {
+        let full_path = self.root.join(normalize(path));
+        self.check_confined(&full_path)?;
+        Ok(full_path)
+    }
+
+    /// Gets the on-disk path of a file for operations that act on
+    /// a symlink itself rather than the file it points to.
+    ///
+    /// Only the parent directory needs to be inside the root directory.
+    fn full_path_nofollow(&self, path: &Path) -> Result<PathBuf, Error> {
+        let full_path = self.root.join(normalize(path));
+
+        match full_path.parent() {
+            Some(parent) if full_path != self.root => self.check_confined(parent)?,
+            _ => return Err(permission_denied(path)),
+        }
+
+        Ok(full_path)
+    }
+
+    /// Makes sure that a path doesn't escape the root through symlinks.
+    ///
+    /// Because the path may not exist yet, we check the deepest
+    /// ancestor of the path that does. Dangling symlinks are always
+    /// denied, as creating a file through one would create it
+    /// wherever the link points.
+    fn check_confined(&self, full_path: &Path) -> Result<(), Error> {
+        let root = fs::canonicalize(&self.root)?;
+        let mut existing_path = full_path;
+
+        loop {
+            match fs::canonicalize(existing_path) {
+                Ok(real_path) => {
+                    return if real_path.starts_with(&root) {
+                        Ok(())
+                    } else {
+                        warn!("denied access to {} as it leads outside of {}",
+                              full_path.display(), self.root.display());
+                        Err(permission_denied(full_path))
+                    };
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                    if fs::symlink_metadata(existing_path).is_ok() {
+                        warn!("denied access to {} through a dangling symlink", full_path.display());
+                        return Err(permission_denied(full_path));
+                    }
+
+                    existing_path = match existing_path.parent() {
+                        Some(parent) => parent,
+                        None => return Err(permission_denied(full_path)),
+                    };
+                },
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Converts on-disk metadata into our own representation.
+    fn convert_metadata(&self, full_path: &Path, metadata: fs::Metadata) -> Result<Metadata, Error> {
+        let file_type = metadata.file_type();
+
+        let (kind, symlink_target) = if file_type.is_symlink() {
+            (FileKind::Symlink, self.symlink_target(full_path)?)
+        } else if file_type.is_dir() {
+            (FileKind::Directory, None)
+        } else {
+            (FileKind::File, None)
+        };
+        let (permissions, owner, group) = ownership(&metadata);
+
+        Ok(Metadata {
+            kind: kind,
+            size: metadata.len(),
+            modified: metadata.modified()?,
+            permissions: permissions,
+            owner: owner,
+            group: group,
+            symlink_target: symlink_target,
+        })
+    }
+
+    /// Reads where a symlink points to.
+    ///
+    /// Absolute targets inside the root are shown relative to it so that
+    /// we don't leak where the root is on disk. Targets outside of the
+    /// root aren't shown at all.
+    fn symlink_target(&self, full_path: &Path) -> Result<Option<PathBuf>, Error> {
+        let target = fs::read_link(full_path)?;
+
+        if target.is_absolute() {
+            let root = fs::canonicalize(&self.root)?;
+            let relative = target.strip_prefix(&self.root).or_else(|_| target.strip_prefix(&root));
+
+            return Ok(relative.ok().map(|relative| Path::new("/").join(relative)));
+        }
+
+        // Relative targets are fine as they are, unless they climb out of the root.
+        let link_dir = full_path.parent().and_then(|parent| parent.strip_prefix(&self.root).ok());
+        let mut depth = match link_dir {
+            Some(link_dir) => link_dir.components().count(),
+            None => return Ok(None),
+        };
+
+        for component in target.components() {
+            match component {
+                Component::Normal(..) => depth += 1,
+                Component::ParentDir if depth == 0 => return Ok(None),
+                Component::ParentDir => depth -= 1,
+                _ => (),
+            }
+        }
+
+        Ok(Some(target))
+    }
 }
 
 impl FileSystem for Physical
 {
     fn list(&self, path: &Path) -> Result<Vec<String>, Error> {
-        let full_path = self.root.join(path);
-
-        let entries: Result<Vec<fs::DirEntry>, _> = fs::read_dir(&full_path)?.collect();
-        let entries = entries?;
+        let full_path = self.full_path(path)?;
 
-        let names: Vec<String>= entries.into_iter().map(|entry| {
-            let base_name = entry.path().strip_prefix(&full_path).unwrap().to_owned();
-            base_name.to_str().unwrap().to_owned()
-        }).collect();
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&full_path)? {
+            // Names that aren't UTF-8 can't be sent over FTP as-is,
+            // so we show them the same way 'ls' would.
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
 
         Ok(names)
     }
 
-    fn create_dir(&mut self, _path: &Path) -> Result<(), Error> {
-        unimplemented!();
+    fn list_entries(&self, path: &Path) -> Result<Vec<DirEntry>, Error> {
+        let full_path = self.full_path(path)?;
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&full_path)? {
+            let entry = entry?;
+            // 'DirEntry::metadata' does not follow symlinks.
+            let metadata = self.convert_metadata(&entry.path(), entry.metadata()?)?;
+
+            entries.push(DirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                metadata: metadata,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        let full_path = self.full_path(path)?;
+        let metadata = fs::metadata(&full_path)?;
+
+        self.convert_metadata(&full_path, metadata)
+    }
+
+    fn create_dir(&mut self, path: &Path) -> Result<(), Error> {
+        fs::create_dir(self.full_path_nofollow(path)?)?;
+        Ok(())
+    }
+
+    fn open_reader(&self, path: &Path, offset: u64) -> Result<Box<Read>, Error> {
+        let mut file = fs::File::open(self.full_path(path)?)?;
+
+        file.seek(io::SeekFrom::Start(offset))?;
+        Ok(Box::new(file))
+    }
+
+    fn open_writer(&mut self, path: &Path, mode: WriteMode) -> Result<Box<Write>, Error> {
+        let full_path = self.full_path(path)?;
+
+        let file = match mode {
+            WriteMode::Truncate => fs::File::create(full_path)?,
+            WriteMode::CreateNew => fs::OpenOptions::new().write(true).create_new(true).open(full_path)?,
+            WriteMode::Append => fs::OpenOptions::new().append(true).create(true).open(full_path)?,
+            WriteMode::Offset(offset) => {
+                let mut file = fs::OpenOptions::new().write(true).open(full_path)?;
+
+                if offset > file.metadata()?.len() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                        format!("offset is past the end of the file: {}", path.display())).into());
+                }
+
+                file.set_len(offset)?;
+                file.seek(io::SeekFrom::Start(offset))?;
+                file
+            },
+        };
+
+        Ok(Box::new(file))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Result<(), Error> {
+        fs::remove_file(self.full_path_nofollow(path)?)?;
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> Result<(), Error> {
+        fs::remove_dir(self.full_path_nofollow(path)?)?;
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        fs::rename(self.full_path_nofollow(from)?, self.full_path_nofollow(to)?)?;
+        Ok(())
+    }
+}
+
+/// Turns a path into one relative to the root directory.
+///
+/// Absolute paths are relative to our root, not the real one, and
+/// '..' can never climb above the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => { normalized.pop(); },
+            Component::RootDir | Component::CurDir | Component::Prefix(..) => (),
+        }
+    }
+
+    normalized
+}
+
+/// Gets the permission bits, owner and group of a file.
+#[cfg(unix)]
+fn ownership(metadata: &fs::Metadata) -> (u32, String, String) {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.mode() & 0o7777, metadata.uid().to_string(), metadata.gid().to_string())
+}
+
+/// Gets the permission bits, owner and group of a file.
+///
+/// Other platforms don't have Unix permissions, so we make them up.
+#[cfg(not(unix))]
+fn ownership(metadata: &fs::Metadata) -> (u32, String, String) {
+    let permissions = if metadata.permissions().readonly() { 0o444 } else { 0o644 };
+    let permissions = if metadata.is_dir() { permissions | 0o111 } else { permissions };
+
+    (permissions, "owner".to_owned(), "group".to_owned())
+}
+
+fn permission_denied(path: &Path) -> Error {
+    io::Error::new(io::ErrorKind::PermissionDenied,
+                   format!("access denied: {}", path.display())).into()
+}
+
+#[cfg(test)]
+mod test
+{
+    pub use super::*;
+
+    use super::super::FileKind;
+    use uuid::Uuid;
+    use std::path::Path;
+    use std::{env, fs};
+
+    /// A temporary directory which is deleted when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir
+    {
+        fn new() -> Self {
+            let path = env::temp_dir().join(format!("flep-{}", Uuid::new_v4().simple()));
+            fs::create_dir_all(path.join("root")).unwrap();
+            TempDir(path)
+        }
+
+        fn file_system(&self) -> Physical { Physical::new(self.0.join("root")) }
+    }
+
+    impl Drop for TempDir
+    {
+        fn drop(&mut self) { fs::remove_dir_all(&self.0).ok(); }
+    }
+
+    #[test]
+    fn correctly_writes_and_reads_a_file() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+        assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,3]);
+    }
+
+    #[test]
+    fn correctly_writes_at_an_offset() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.write_file(&Path::new("/foo.txt"), vec![1,2,3,4]).unwrap();
+        fs.open_writer(&Path::new("/foo.txt"), WriteMode::Offset(2)).unwrap().write_all(&[5]).unwrap();
+        assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2,5]);
+
+        assert!(fs.open_writer(&Path::new("/foo.txt"), WriteMode::Offset(4)).is_err());
+    }
+
+    #[test]
+    fn only_creates_new_files_when_asked() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.open_writer(&Path::new("/foo.txt"), WriteMode::CreateNew).unwrap().write_all(&[1,2]).unwrap();
+        assert!(fs.open_writer(&Path::new("/foo.txt"), WriteMode::CreateNew).is_err());
+        assert_eq!(fs.read_file(&Path::new("/foo.txt")).unwrap(), vec![1,2]);
+    }
+
+    #[test]
+    fn correctly_reads_from_an_offset() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+        let mut data = Vec::new();
+
+        fs.write_file(&Path::new("/foo.txt"), vec![1,2,3]).unwrap();
+        fs.open_reader(&Path::new("/foo.txt"), 1).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, vec![2,3]);
+    }
+
+    #[test]
+    fn correctly_creates_and_lists_a_directory() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.create_dir(&Path::new("/foo")).unwrap();
+        fs.write_file(&Path::new("/foo/bar.txt"), vec![1,2,3]).unwrap();
+
+        assert_eq!(fs.list(&Path::new("/foo")).unwrap(), vec!["bar.txt".to_owned()]);
+    }
+
+    #[test]
+    fn correctly_gets_metadata() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.create_dir(&Path::new("/foo")).unwrap();
+        fs.write_file(&Path::new("/foo/bar.txt"), vec![1,2,3]).unwrap();
+
+        let file = fs.metadata(&Path::new("/foo/bar.txt")).unwrap();
+        assert_eq!(file.kind, FileKind::File);
+        assert_eq!(file.size, 3);
+
+        assert_eq!(fs.metadata(&Path::new("/foo")).unwrap().kind, FileKind::Directory);
+        assert!(fs.metadata(&Path::new("/missing")).is_err());
+    }
+
+    #[test]
+    fn correctly_lists_entries() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.write_file(&Path::new("/bar.txt"), vec![1,2,3]).unwrap();
+
+        let entries = fs.list_entries(&Path::new("/")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "bar.txt");
+        assert_eq!(entries[0].metadata.size, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lists_symlinks_without_following_them() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.write_file(&Path::new("/bar.txt"), vec![1,2,3]).unwrap();
+        symlink(dir.0.join("root").join("bar.txt"), dir.0.join("root").join("link")).unwrap();
+
+        let entries = fs.list_entries(&Path::new("/")).unwrap();
+        let link = entries.iter().find(|entry| entry.name == "link").unwrap();
+
+        assert_eq!(link.metadata.kind, FileKind::Symlink);
+        assert_eq!(link.metadata.symlink_target, Some(PathBuf::from("/bar.txt")));
+        assert_eq!(fs.metadata(&Path::new("/link")).unwrap().kind, FileKind::File);
+    }
+
+    #[test]
+    fn returns_errors_for_missing_files() {
+        let dir = TempDir::new();
+        let fs = dir.file_system();
+
+        assert!(fs.read_file(&Path::new("/missing.txt")).is_err());
+        assert!(fs.list(&Path::new("/missing")).is_err());
+    }
+
+    #[test]
+    fn cannot_escape_the_root_through_parent_directories() {
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs.write_file(&Path::new("/../../escaped.txt"), vec![1,2,3]).unwrap();
+
+        assert!(dir.0.join("root").join("escaped.txt").exists());
+        assert!(!dir.0.join("escaped.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cannot_escape_the_root_through_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs::create_dir(dir.0.join("outside")).unwrap();
+        fs::write(dir.0.join("outside").join("secret.txt"), "secret").unwrap();
+        symlink(dir.0.join("outside"), dir.0.join("root").join("link")).unwrap();
+
+        assert!(fs.read_file(&Path::new("/link/secret.txt")).is_err());
+        assert!(fs.list(&Path::new("/link")).is_err());
+        assert!(fs.write_file(&Path::new("/link/new.txt"), vec![1]).is_err());
+        assert!(!dir.0.join("outside").join("new.txt").exists());
+
+        // The link itself can still be removed.
+        fs.remove_file(&Path::new("/link")).unwrap();
     }
 
-    fn write_file(&mut self, _path: &Path, _data: Vec<u8>) -> Result<(), Error> {
-        unimplemented!();
+    #[cfg(unix)]
+    #[test]
+    fn cannot_create_files_outside_the_root_through_dangling_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new();
+        let mut fs = dir.file_system();
+
+        fs::create_dir(dir.0.join("outside")).unwrap();
+        symlink(dir.0.join("outside").join("new.txt"), dir.0.join("root").join("link")).unwrap();
+
+        assert!(fs.write_file(&Path::new("/link"), vec![1]).is_err());
+        assert!(fs.append_file(&Path::new("/link"), vec![1]).is_err());
+        assert!(!dir.0.join("outside").join("new.txt").exists());
     }
 
-    fn read_file(&self, _path: &Path) -> Result<Vec<u8>, Error> {
-        unimplemented!();
+    #[cfg(unix)]
+    #[test]
+    fn does_not_show_symlink_targets_outside_the_root() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new();
+        let fs = dir.file_system();
+
+        symlink(dir.0.join("outside"), dir.0.join("root").join("absolute")).unwrap();
+        symlink("../outside", dir.0.join("root").join("relative")).unwrap();
+        symlink("foo.txt", dir.0.join("root").join("inside")).unwrap();
+
+        let mut entries = fs.list_entries(&Path::new("/")).unwrap();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let targets: Vec<_> = entries.iter().map(|entry| entry.metadata.symlink_target.clone()).collect();
+        assert_eq!(targets, vec![None, Some("foo.txt".into()), None]);
     }
 }
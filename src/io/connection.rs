@@ -1,9 +1,17 @@
 use Error;
 use protocol;
-use super::Io;
+use super::{Io, LineBuffer, Stream};
 
 use mio::tcp::{TcpStream, TcpListener};
+use mio::unix::UnixReady;
 use mio;
+use net2::TcpBuilder;
+
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::time::Instant;
+use std::io::prelude::*;
+use std::io;
 
 /// An FTP connection
 pub struct Connection
@@ -12,13 +20,18 @@ pub struct Connection
     pub dtp: DataTransfer,
 }
 
+/// The longest command line we accept, including the CRLF.
+const MAX_COMMAND_LINE_LENGTH: usize = 4096;
+
 /// The protocol interpreter (PI) stream.
 pub struct Interpreter
 {
-    /// The underlying socket.
-    pub stream: TcpStream,
+    /// The underlying stream.
+    pub stream: Stream,
     /// The token used to listen for events on the PI stream.
     pub token: mio::Token,
+    /// The bytes received that haven't been handled yet.
+    pub input: LineBuffer,
 }
 
 /// The data transfer prototocol (DTP) stream.
@@ -32,7 +45,13 @@ pub enum DataTransfer
         listener: TcpListener,
         /// The token for the listener.
         token: mio::Token,
+        /// When we started waiting for the client to connect.
+        ///
+        /// This is reset when a transfer is requested, which is
+        /// when the client is expected to connect.
+        waiting_since: Instant,
     },
+    /// We are connecting to the client, for active mode.
     Connecting {
         /// The underlying socket.
         stream: TcpStream,
@@ -41,8 +60,8 @@ pub enum DataTransfer
     },
     /// We are connected.
     Connected {
-        /// The underlying socket.
-        stream: TcpStream,
+        /// The underlying stream.
+        stream: Stream,
         /// The token used to listen for events on the DTP stream.
         token: mio::Token,
     },
@@ -62,14 +81,20 @@ impl Connection
 {
     pub fn send_command<C>(&mut self, command: &C) -> Result<(), Error>
         where C: protocol::Command {
-        command.write(&mut self.pi.stream)?;
+        let mut buffer = command.bytes()?;
+        buffer.extend_from_slice(b"\r\n");
+
+        self.pi.stream.write_all(&buffer)?;
         Ok(())
     }
 
     pub fn send_reply<R>(&mut self, reply: R) -> Result<(), Error>
         where R: Into<protocol::Reply> {
-        let reply = reply.into();
-        reply.write(&mut self.pi.stream)?;
+        // Written in one go so that a TLS session sends it in one record.
+        let mut buffer = Vec::new();
+        reply.into().write(&mut buffer)?;
+
+        self.pi.stream.write_all(&buffer)?;
         Ok(())
     }
 
@@ -85,28 +110,109 @@ impl Connection
     }
 }
 
+impl Interpreter
+{
+    /// Creates a new protocol interpreter for a control connection.
+    pub fn new(stream: Stream, token: mio::Token) -> Self {
+        Interpreter {
+            stream: stream,
+            token: token,
+            input: LineBuffer::new(MAX_COMMAND_LINE_LENGTH),
+        }
+    }
+
+    /// Gets the address the client connected to.
+    pub fn local_ip(&self) -> Result<IpAddr, io::Error> {
+        Ok(unmap_ip(self.stream.socket().local_addr()?.ip()))
+    }
+
+    /// Gets the address the client connected from.
+    pub fn peer_ip(&self) -> Result<IpAddr, io::Error> {
+        Ok(unmap_ip(self.stream.socket().peer_addr()?.ip()))
+    }
+}
+
 impl DataTransfer
 {
-    /// Start listening for a new data transfer on a port.
-    pub fn bind(port: u16, io: &mut Io) -> Result<Self, Error> {
-        use std::net::ToSocketAddrs;
+    /// Start listening for a new data transfer on a free port.
+    ///
+    /// The port stays in use for as long as we are listening on it,
+    /// and is released once the data connection is made or abandoned.
+    ///
+    /// Returns the port that was picked.
+    pub fn listen(ip: IpAddr, ports: RangeInclusive<u16>, io: &mut Io) -> Result<(Self, u16), Error> {
+        for _ in ports.clone() {
+            let port = io.next_passive_port(&ports);
+
+            let listener = match TcpListener::bind(&SocketAddr::new(ip, port)) {
+                Ok(listener) => listener,
+                Err(ref e) if e.kind() == io::ErrorKind::AddrInUse => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let token = io.allocate_token();
+            io.poll.register(&listener, token, mio::Ready::readable(),
+                             mio::PollOpt::edge())?;
+
+            return Ok((DataTransfer::Listening {
+                listener: listener,
+                token: token,
+                waiting_since: Instant::now(),
+            }, port));
+        }
 
-        let addr = ("127.0.0.1", port).to_socket_addrs()?.next().unwrap();
-        let listener = TcpListener::bind(&addr)?;
+        Err(io::Error::new(io::ErrorKind::AddrInUse, "all passive ports are in use").into())
+    }
 
-        let token = io.allocate_token();
+    /// Start opening a data connection to the client, for active mode.
+    ///
+    /// The connection is made from `local_addr` if it is given.
+    pub fn connect(addr: &SocketAddr, local_addr: Option<SocketAddr>, io: &mut Io)
+        -> Result<Self, Error> {
+        let stream = match local_addr {
+            Some(local_addr) => {
+                let builder = match local_addr {
+                    SocketAddr::V4(..) => TcpBuilder::new_v4()?,
+                    SocketAddr::V6(..) => TcpBuilder::new_v6()?,
+                };
+                // Connections to different clients all come from the same port.
+                builder.reuse_address(true)?;
+                builder.bind(local_addr)?;
+
+                TcpStream::connect_stream(builder.to_tcp_stream()?, addr)?
+            },
+            None => TcpStream::connect(addr)?,
+        };
 
-        io.poll.register(&listener, token, mio::Ready::readable(),
-                      mio::PollOpt::edge())?;
+        let token = io.allocate_token();
+        io.poll.register(&stream, token,
+                         mio::Ready::readable() | UnixReady::hup() |
+                         mio::Ready::writable(),
+                         mio::PollOpt::edge())?;
 
-        Ok(DataTransfer::Listening {
-            listener: listener,
+        Ok(DataTransfer::Connecting {
+            stream: stream,
             token: token,
         })
     }
+}
 
-    pub fn connect() -> Result<Self, Error> {
-        unimplemented!();
+/// Turns an IPv4-mapped IPv6 address back into an IPv4 address.
+///
+/// IPv4 clients connecting to a dual-stack socket show up with
+/// addresses like `::ffff:127.0.0.1`.
+pub fn unmap_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+
+            if segments[0..5].iter().all(|&s| s == 0) && segments[5] == 0xffff {
+                IpAddr::V4(v6.to_ipv4().unwrap())
+            } else {
+                IpAddr::V6(v6)
+            }
+        },
+        ip => ip,
     }
 }
 
@@ -115,3 +221,28 @@ impl Default for DataTransferMode
     // FTP defaults to active mode (unless you send 'PASV').
     fn default() -> Self { DataTransferMode::Active }
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use std::net;
+
+    #[test]
+    fn skips_passive_ports_that_are_in_use() {
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let taken = net::TcpListener::bind((ip, 0)).unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        // We need the port after it to be free.
+        if port == 65535 || net::TcpListener::bind((ip, port + 1)).is_err() {
+            return;
+        }
+
+        let mut io = Io::new().unwrap();
+        let (_, listening_port) = DataTransfer::listen(ip, port..=port + 1, &mut io).unwrap();
+        assert_eq!(listening_port, port + 1);
+
+        assert!(DataTransfer::listen(ip, port..=port, &mut io).is_err());
+    }
+}
@@ -1,10 +1,14 @@
 use Error;
 use mio::*;
 
+use std::ops::RangeInclusive;
+
 pub struct Io
 {
     pub poll: Poll,
     token_accumulator: usize,
+    /// How many passive ports we have handed out.
+    passive_port_accumulator: usize,
 }
 
 impl Io
@@ -13,6 +17,7 @@ impl Io
         Ok(Io {
             poll: Poll::new()?,
             token_accumulator: 100,
+            passive_port_accumulator: 0,
         })
     }
 
@@ -20,4 +25,30 @@ impl Io
         self.token_accumulator += 1;
         Token(self.token_accumulator)
     }
+
+    /// Picks the next port to try listening on for a passive data connection.
+    ///
+    /// Ports are handed out in turn so that a port that was just
+    /// released is the last to be picked again.
+    pub fn next_passive_port(&mut self, ports: &RangeInclusive<u16>) -> u16 {
+        let port_count = (ports.end() - ports.start()) as usize + 1;
+        let port = ports.start() + (self.passive_port_accumulator % port_count) as u16;
+
+        self.passive_port_accumulator += 1;
+        port
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn hands_out_passive_ports_in_turn() {
+        let mut io = Io::new().unwrap();
+        let ports: Vec<_> = (0..5).map(|_| io.next_passive_port(&(65533..=65535))).collect();
+
+        assert_eq!(ports, vec![65533, 65534, 65535, 65533, 65534]);
+    }
 }
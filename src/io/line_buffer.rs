@@ -0,0 +1,205 @@
+use super::telnet::TelnetFilter;
+
+use std::collections::VecDeque;
+use std::mem;
+
+/// A line taken from a `LineBuffer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line
+{
+    /// A complete line, including the line ending.
+    Complete(Vec<u8>),
+    /// A line longer than the limit, which is thrown away.
+    TooLong,
+}
+
+/// Collects the bytes received on the control connection into lines.
+///
+/// Commands can be split across reads, and several commands can
+/// arrive in a single read when clients pipeline them. Lines should
+/// end with CRLF, but we also accept a bare LF like most servers.
+///
+/// Lines are split up as bytes are pushed, so that no more than
+/// one line's worth of an unfinished line is ever kept.
+#[derive(Clone, Debug)]
+pub struct LineBuffer
+{
+    /// The lines received that haven't been taken yet.
+    lines: VecDeque<Line>,
+    /// The start of a line that hasn't been received in full yet.
+    partial_line: Vec<u8>,
+    /// The longest a line can be, including the line ending.
+    max_line_length: usize,
+    /// Removes Telnet commands before they reach the buffer.
+    telnet: TelnetFilter,
+    /// Whether we are throwing away the rest of a line that is too long.
+    discarding: bool,
+}
+
+impl LineBuffer
+{
+    /// Creates a new line buffer.
+    pub fn new(max_line_length: usize) -> Self {
+        LineBuffer {
+            lines: VecDeque::new(),
+            partial_line: Vec::new(),
+            max_line_length: max_line_length,
+            telnet: TelnetFilter::new(),
+            discarding: false,
+        }
+    }
+
+    /// Adds bytes received from the network.
+    pub fn push(&mut self, bytes: &[u8]) {
+        let mut text = Vec::with_capacity(bytes.len());
+        self.telnet.filter(bytes, &mut text);
+
+        for byte in text {
+            if self.discarding {
+                // This is the end of a line we have already reported.
+                if byte == b'\n' { self.discarding = false; }
+                continue;
+            }
+
+            self.partial_line.push(byte);
+
+            if byte == b'\n' {
+                let line = mem::replace(&mut self.partial_line, Vec::new());
+                self.lines.push_back(Line::Complete(line));
+            } else if self.partial_line.len() >= self.max_line_length {
+                // There is no room left for the line ending.
+                self.partial_line.clear();
+                self.lines.push_back(Line::TooLong);
+                self.discarding = true;
+            }
+        }
+    }
+
+    /// Takes the next line out of the buffer.
+    ///
+    /// Returns `None` if there is no complete line yet. A line that is
+    /// too long is reported once, as soon as we know, and the rest of
+    /// it is skipped.
+    pub fn next_line(&mut self) -> Option<Line> {
+        self.lines.pop_front()
+    }
+
+    /// Throws away everything in the buffer.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.partial_line.clear();
+        self.telnet = TelnetFilter::new();
+        self.discarding = false;
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn complete(text: &str) -> Option<Line> {
+        Some(Line::Complete(text.as_bytes().to_owned()))
+    }
+
+    #[test]
+    fn keeps_partial_lines() {
+        let mut buffer = LineBuffer::new(100);
+        buffer.push(b"USER b");
+        assert_eq!(buffer.next_line(), None);
+
+        buffer.push(b"ob\r");
+        assert_eq!(buffer.next_line(), None);
+
+        buffer.push(b"\n");
+        assert_eq!(buffer.next_line(), complete("USER bob\r\n"));
+        assert_eq!(buffer.next_line(), None);
+    }
+
+    #[test]
+    fn gives_pipelined_lines_in_order() {
+        let mut buffer = LineBuffer::new(100);
+        buffer.push(b"USER bob\r\nPASS secret\r\nPW");
+
+        assert_eq!(buffer.next_line(), complete("USER bob\r\n"));
+        assert_eq!(buffer.next_line(), complete("PASS secret\r\n"));
+        assert_eq!(buffer.next_line(), None);
+
+        buffer.push(b"D\r\n");
+        assert_eq!(buffer.next_line(), complete("PWD\r\n"));
+    }
+
+    #[test]
+    fn accepts_bare_line_feeds() {
+        let mut buffer = LineBuffer::new(100);
+        buffer.push(b"USER bob\nPWD\r\n");
+
+        assert_eq!(buffer.next_line(), complete("USER bob\n"));
+        assert_eq!(buffer.next_line(), complete("PWD\r\n"));
+    }
+
+    #[test]
+    fn removes_telnet_commands() {
+        let mut buffer = LineBuffer::new(100);
+        buffer.push(b"\xff\xf4\xff\xf2AB");
+        buffer.push(b"OR\r\n");
+
+        assert_eq!(buffer.next_line(), complete("ABOR\r\n"));
+    }
+
+    #[test]
+    fn rejects_long_complete_lines() {
+        let mut buffer = LineBuffer::new(8);
+        buffer.push(b"NOOP\r\nUSER somebody\r\nPWD\r\n");
+
+        assert_eq!(buffer.next_line(), complete("NOOP\r\n"));
+        assert_eq!(buffer.next_line(), Some(Line::TooLong));
+        assert_eq!(buffer.next_line(), complete("PWD\r\n"));
+    }
+
+    #[test]
+    fn skips_the_rest_of_long_partial_lines() {
+        let mut buffer = LineBuffer::new(8);
+        buffer.push(b"USER some");
+        assert_eq!(buffer.next_line(), Some(Line::TooLong));
+
+        buffer.push(b"body who has a long name\r");
+        assert_eq!(buffer.next_line(), None);
+
+        buffer.push(b"\nPWD\r\n");
+        assert_eq!(buffer.next_line(), complete("PWD\r\n"));
+        assert_eq!(buffer.next_line(), None);
+    }
+
+    #[test]
+    fn reports_long_lines_in_order() {
+        let mut buffer = LineBuffer::new(8);
+        buffer.push(b"NOOP\r\nUSER some");
+        buffer.push(b"body\r\nPWD\r\n");
+
+        assert_eq!(buffer.next_line(), complete("NOOP\r\n"));
+        assert_eq!(buffer.next_line(), Some(Line::TooLong));
+        assert_eq!(buffer.next_line(), complete("PWD\r\n"));
+    }
+
+    #[test]
+    fn stays_bounded_when_a_long_line_arrives_at_once() {
+        let mut buffer = LineBuffer::new(8);
+        buffer.push(&[b'a'; 10000]);
+
+        assert!(buffer.partial_line.len() <= 8);
+        assert_eq!(buffer.lines.len(), 1);
+        assert_eq!(buffer.next_line(), Some(Line::TooLong));
+        assert_eq!(buffer.next_line(), None);
+    }
+
+    #[test]
+    fn clear_throws_away_buffered_lines() {
+        let mut buffer = LineBuffer::new(100);
+        buffer.push(b"AUTH TLS\r\nUSER injected\r\n");
+
+        assert_eq!(buffer.next_line(), complete("AUTH TLS\r\n"));
+        buffer.clear();
+        assert_eq!(buffer.next_line(), None);
+    }
+}
@@ -1,9 +1,15 @@
 //! Input/output related functionality.
 
 pub use self::connection::{Connection, Interpreter, DataTransfer,
-                           DataTransferMode};
+                           DataTransferMode, unmap_ip};
 pub use self::io::Io;
+pub use self::line_buffer::{LineBuffer, Line};
+pub use self::stream::Stream;
 
+pub mod tls;
 mod connection;
 mod io;
+mod line_buffer;
+mod stream;
+mod telnet;
 
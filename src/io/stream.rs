@@ -0,0 +1,189 @@
+use mio::tcp::TcpStream;
+use rustls::{self, Session};
+
+use std::io::prelude::*;
+use std::net::Shutdown;
+use std::sync::Arc;
+use std::io;
+
+/// A TCP stream which may be protected with TLS.
+///
+/// TLS records are only read and written as the socket allows, so
+/// the stream can be used without blocking, just like the socket.
+pub struct Stream
+{
+    /// The underlying socket.
+    socket: TcpStream,
+    /// The TLS session, if the stream is protected.
+    tls: Option<rustls::ServerSession>,
+    /// Whether we have told the other end we are closing the session.
+    sent_close_notify: bool,
+}
+
+impl Stream
+{
+    /// Creates a new unprotected stream.
+    pub fn new(socket: TcpStream) -> Self {
+        Stream {
+            socket: socket,
+            tls: None,
+            sent_close_notify: false,
+        }
+    }
+
+    /// Starts protecting the stream with TLS, as the server.
+    ///
+    /// The handshake happens as the stream is read from and written to.
+    pub fn start_tls(&mut self, config: &Arc<rustls::ServerConfig>) {
+        self.tls = Some(rustls::ServerSession::new(config));
+    }
+
+    /// Checks if the stream is protected with TLS.
+    pub fn is_secure(&self) -> bool { self.tls.is_some() }
+
+    /// Continues the TLS handshake as far as the socket allows.
+    ///
+    /// Gives `WouldBlock` until the handshake is finished.
+    pub fn handshake(&mut self) -> io::Result<()> {
+        match self.tls {
+            Some(ref mut session) => {
+                complete_handshake(session, &mut self.socket)?;
+                write_pending(session, &mut self.socket)
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the underlying socket.
+    pub fn socket(&self) -> &TcpStream { &self.socket }
+
+    /// Shuts down the writing half of the stream.
+    ///
+    /// TLS sessions are closed with a 'close_notify' alert first, so this
+    /// gives `WouldBlock` until the alert and everything before it is sent.
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        if let Some(ref mut session) = self.tls {
+            complete_handshake(session, &mut self.socket)?;
+
+            if !self.sent_close_notify {
+                session.send_close_notify();
+                self.sent_close_notify = true;
+            }
+
+            write_pending(session, &mut self.socket)?;
+        }
+
+        self.socket.shutdown(Shutdown::Write)
+    }
+}
+
+impl Read for Stream
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let session = match self.tls {
+            Some(ref mut session) => session,
+            None => return self.socket.read(buf),
+        };
+
+        loop {
+            match session.read(buf) {
+                Ok(0) => (),
+                Ok(bytes_read) => return Ok(bytes_read),
+                // The other end sent 'close_notify'.
+                Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => return Ok(0),
+                Err(e) => return Err(e),
+            }
+
+            // The other end may be waiting on us to continue the handshake.
+            ignore_would_block(write_pending(session, &mut self.socket))?;
+
+            if read_tls(session, &mut self.socket)? == 0 {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+impl Write for Stream
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let session = match self.tls {
+            Some(ref mut session) => session,
+            None => return self.socket.write(buf),
+        };
+
+        // Until the handshake is finished, the session holds
+        // on to what we write, up to a limit.
+        ignore_would_block(complete_handshake(session, &mut self.socket))?;
+
+        // We only take more data once the socket has taken everything
+        // so far, otherwise the session would buffer without limit.
+        write_pending(session, &mut self.socket)?;
+
+        let bytes_written = session.write(buf)?;
+        if bytes_written == 0 && !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "waiting for TLS handshake"));
+        }
+
+        ignore_would_block(write_pending(session, &mut self.socket))?;
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.tls {
+            Some(ref mut session) => {
+                session.flush()?;
+                write_pending(session, &mut self.socket)
+            },
+            None => self.socket.flush(),
+        }
+    }
+}
+
+/// Continues the TLS handshake until it is finished.
+fn complete_handshake(session: &mut rustls::ServerSession,
+                      socket: &mut TcpStream) -> io::Result<()> {
+    while session.is_handshaking() {
+        write_pending(session, socket)?;
+
+        if read_tls(session, socket)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "connection closed during TLS handshake"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads TLS records from the socket into the session.
+///
+/// Returns the number of bytes read, which is zero at EOF.
+fn read_tls(session: &mut rustls::ServerSession,
+            socket: &mut TcpStream) -> io::Result<usize> {
+    let bytes_read = session.read_tls(socket)?;
+
+    if let Err(e) = session.process_new_packets() {
+        // Try to let the other end know what went wrong.
+        let _ = write_pending(session, socket);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+    }
+
+    Ok(bytes_read)
+}
+
+/// Writes the TLS records the session has waiting to the socket.
+fn write_pending(session: &mut rustls::ServerSession,
+                 socket: &mut TcpStream) -> io::Result<()> {
+    while session.wants_write() {
+        session.write_tls(socket)?;
+    }
+
+    Ok(())
+}
+
+fn ignore_would_block(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,114 @@
+//! Handling of the Telnet protocol used by the control connection.
+//!
+//! * [RFC 854](https://tools.ietf.org/html/rfc854)
+
+/// Interpret as command.
+const IAC: u8 = 255;
+/// The first byte of an option negotiation ('WILL', 'WONT', 'DO' and 'DONT').
+const WILL: u8 = 251;
+/// The last byte of an option negotiation.
+const DONT: u8 = 254;
+/// Subnegotiation begin.
+const SB: u8 = 250;
+/// Subnegotiation end.
+const SE: u8 = 240;
+
+/// Where we are in a Telnet command sequence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State
+{
+    /// Reading normal text.
+    Text,
+    /// Just read 'IAC'.
+    Command,
+    /// Just read 'IAC WILL' or similar, the option is next.
+    Option,
+    /// Inside a subnegotiation, which ends with 'IAC SE'.
+    Subnegotiation,
+    /// Just read 'IAC' inside a subnegotiation.
+    SubnegotiationCommand,
+}
+
+/// Removes Telnet commands from the control connection.
+///
+/// Clients send 'IP' (interrupt process) and 'Synch' before 'ABOR',
+/// and some negotiate options, none of which are part of the FTP
+/// commands themselves. Commands can be split across reads.
+#[derive(Clone, Debug)]
+pub struct TelnetFilter
+{
+    state: State,
+}
+
+impl TelnetFilter
+{
+    /// Creates a new filter.
+    pub fn new() -> Self {
+        TelnetFilter { state: State::Text }
+    }
+
+    /// Filters received bytes, appending the text to `output`.
+    pub fn filter(&mut self, bytes: &[u8], output: &mut Vec<u8>) {
+        for &byte in bytes {
+            self.state = match (self.state, byte) {
+                (State::Text, IAC) => State::Command,
+                (State::Text, _) => { output.push(byte); State::Text },
+                // An escaped 255 byte.
+                (State::Command, IAC) => { output.push(byte); State::Text },
+                (State::Command, b) if b >= WILL && b <= DONT => State::Option,
+                (State::Command, SB) => State::Subnegotiation,
+                (State::Command, b) if b >= SE => State::Text,
+                // Not a Telnet command, which happens when the 'DM' of
+                // a 'Synch' is sent as urgent data and skipped over.
+                (State::Command, _) => { output.push(byte); State::Text },
+                (State::Option, _) => State::Text,
+                (State::Subnegotiation, IAC) => State::SubnegotiationCommand,
+                (State::Subnegotiation, _) => State::Subnegotiation,
+                (State::SubnegotiationCommand, SE) => State::Text,
+                (State::SubnegotiationCommand, _) => State::Subnegotiation,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn filter(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut filter = TelnetFilter::new();
+        let mut output = Vec::new();
+
+        for chunk in chunks { filter.filter(chunk, &mut output) }
+        output
+    }
+
+    #[test]
+    fn keeps_plain_text() {
+        assert_eq!(filter(&[b"USER bob\r\n"]), b"USER bob\r\n");
+    }
+
+    #[test]
+    fn removes_interrupts() {
+        // 'IP' followed by 'Synch', which is 'IAC DM'.
+        assert_eq!(filter(&[b"\xff\xf4\xff\xf2ABOR\r\n"]), b"ABOR\r\n");
+        // The same when the 'DM' was sent as urgent data and skipped.
+        assert_eq!(filter(&[b"\xff\xf4\xff", b"ABOR\r\n"]), b"ABOR\r\n");
+    }
+
+    #[test]
+    fn removes_option_negotiation() {
+        assert_eq!(filter(&[b"\xff\xfd\x01NO", b"OP\xff", b"\xfb", b"\x03\r\n"]), b"NOOP\r\n");
+    }
+
+    #[test]
+    fn removes_subnegotiation() {
+        assert_eq!(filter(&[b"\xff\xfa\x18\x01\xff", b"\xf0PWD\r\n"]), b"PWD\r\n");
+    }
+
+    #[test]
+    fn unescapes_iac() {
+        assert_eq!(filter(&[b"a\xff\xffb"]), b"a\xffb");
+    }
+}
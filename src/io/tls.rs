@@ -0,0 +1,55 @@
+//! TLS configuration.
+
+use {Error, ErrorKind};
+
+use rustls::internal::pemfile;
+use rustls;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Creates a TLS configuration from PEM files.
+///
+/// The certificate file should contain the server's certificate,
+/// followed by any intermediate certificates. The private key can
+/// be either a PKCS #8 or an RSA key.
+pub fn server_config(certificate_path: &Path, private_key_path: &Path)
+    -> Result<Arc<rustls::ServerConfig>, Error> {
+    let certificates = {
+        let mut reader = BufReader::new(File::open(certificate_path)?);
+        pemfile::certs(&mut reader).map_err(|_| invalid("could not read certificates"))?
+    };
+
+    if certificates.is_empty() {
+        return Err(invalid("no certificates found"));
+    }
+
+    let private_key = {
+        let mut reader = BufReader::new(File::open(private_key_path)?);
+        let mut keys = pemfile::pkcs8_private_keys(&mut reader)
+            .map_err(|_| invalid("could not read private key"))?;
+
+        if keys.is_empty() {
+            let mut reader = BufReader::new(File::open(private_key_path)?);
+            keys = pemfile::rsa_private_keys(&mut reader)
+                .map_err(|_| invalid("could not read private key"))?;
+        }
+
+        match keys.into_iter().next() {
+            Some(key) => key,
+            None => return Err(invalid("no private key found")),
+        }
+    };
+
+    let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+    config.set_single_cert(certificates, private_key)
+        .map_err(|e| invalid(&e.to_string()))?;
+
+    Ok(Arc::new(config))
+}
+
+fn invalid(reason: &str) -> Error {
+    ErrorKind::InvalidTlsConfiguration(reason.to_owned()).into()
+}
@@ -1,13 +1,17 @@
-//! FTP server library.
+//! FTP server and client library.
 
 pub extern crate flep_protocol as protocol;
+pub extern crate rustls;
 
 extern crate mio;
+extern crate net2;
 extern crate uuid;
 #[macro_use]
 extern crate error_chain;
 #[macro_use]
 extern crate log;
+#[cfg(test)]
+extern crate webpki;
 
 pub use self::credentials::Credentials;
 pub use self::errors::*;
@@ -15,6 +19,7 @@ pub use self::errors::*;
 pub use protocol::FileType;
 
 pub mod server;
+pub mod client;
 pub mod io;
 pub mod fs;
 pub mod util;
@@ -12,11 +12,12 @@ pub trait Argument : Sized
     /// Most argument types don't care about leading spaces. These
     /// types can instead override `read`.
     fn read_with_space(read: &mut BufRead) -> Result<Self, Error> {
-        let mut buf: [u8; 1] = [0];
-
-        // Check if we can read a byte
-        assert_eq!(read.read(&mut buf)?, 1, "unexpected EOF while checking for space");
-        assert_eq!(buf[0] as char, ' ', "expected space preceding argument");
+        match read_char(read)? {
+            Some(' ') => (),
+            Some(c) => return Err(ErrorKind::InvalidArgument(
+                format!("expected space preceding argument, got '{}'", c.escape_default())).into()),
+            None => return Err(ErrorKind::InvalidArgument("missing argument".to_owned()).into()),
+        }
 
         // Now that we've trimmed the space, delegate downwards.
         Self::read(read)
@@ -30,14 +31,28 @@ pub trait Argument : Sized
         Self::read_with_space(&mut buffer).unwrap()
     }
 
-    fn bytes(&self) -> Vec<u8> {
+    /// Writes the argument to a buffer.
+    fn bytes(&self) -> Result<Vec<u8>, Error> {
         let mut buffer = io::Cursor::new(Vec::new());
-        self.write(&mut buffer).unwrap();
-        buffer.into_inner()
+        self.write(&mut buffer)?;
+        Ok(buffer.into_inner())
     }
 
-    fn to_string(&self) -> String {
-        String::from_utf8(self.bytes()).unwrap()
+    fn to_string(&self) -> Result<String, Error> {
+        Ok(String::from_utf8(self.bytes()?)?)
+    }
+}
+
+/// Reads a single character of an argument.
+///
+/// Returns `None` at the end of the command line.
+pub fn read_char(read: &mut BufRead) -> Result<Option<char>, Error> {
+    let mut buf: [u8; 1] = [0];
+
+    if read.read(&mut buf)? == 1 {
+        Ok(Some(buf[0] as char))
+    } else {
+        Ok(None)
     }
 }
 
@@ -54,7 +69,12 @@ impl Argument for String
     }
 
     fn write(&self, write: &mut Write) -> Result<(), Error> {
-        for c in self.chars() { assert!(c.is_ascii(), "only ASCII is supported in FTP"); }
+        // Text is sent as UTF-8, as RFC 2640 asks for, but a line
+        // break would end the command early and start another.
+        if self.contains(|c| c == '\r' || c == '\n') {
+            return Err(ErrorKind::InvalidArgument("argument contains a line break".to_owned()).into());
+        }
+
         write!(write, "{}", self)?;
         Ok(())
     }
@@ -108,9 +128,9 @@ impl<T: Argument> Argument for Option<T>
         }
     }
 
-    fn read(_read: &mut BufRead) -> Result<Self, Error> {
+    fn read(read: &mut BufRead) -> Result<Self, Error> {
         // We override a higher level method - read_with_space
-        unreachable!();
+        T::read(read).map(Some)
     }
 
     fn write(&self, write: &mut Write) -> Result<(), Error> {
@@ -151,13 +171,69 @@ mod test
 
         #[test]
         fn correctly_writes_a_present_value() {
-            assert_eq!(Some("foo".to_owned()).to_string(), " foo");
+            assert_eq!(Some("foo".to_owned()).to_string().unwrap(), " foo");
         }
 
         #[test]
         fn correctly_writes_an_empty_value() {
             let value: Option<String> = None;
-            assert_eq!(value.to_string(), "");
+            assert_eq!(value.to_string().unwrap(), "");
+        }
+    }
+
+    mod string
+    {
+        pub use super::*;
+
+        fn write(text: &str) -> Result<Vec<u8>, Error> {
+            let mut buffer = Vec::new();
+            text.to_owned().write(&mut buffer)?;
+            Ok(buffer)
+        }
+
+        #[test]
+        fn correctly_writes_ascii() {
+            assert_eq!(write("foo.txt").unwrap(), b"foo.txt");
+        }
+
+        #[test]
+        fn correctly_writes_utf8() {
+            assert_eq!(write("caf\u{e9}").unwrap(), "caf\u{e9}".as_bytes());
+        }
+
+        #[test]
+        fn refuses_to_write_line_breaks() {
+            match write("foo\r\nDELE bar") {
+                Err(Error(ErrorKind::InvalidArgument(..), _)) => (),
+                result => panic!("unexpected result: {:?}", result),
+            }
+            assert!(write("foo\n").is_err());
+        }
+    }
+
+    mod required
+    {
+        use std::io;
+        pub use super::*;
+
+        fn parse(text: &str) -> Result<String, Error> {
+            Argument::read_with_space(&mut io::Cursor::new(text))
+        }
+
+        #[test]
+        fn rejects_a_missing_value() {
+            match parse("") {
+                Err(Error(ErrorKind::InvalidArgument(..), _)) => (),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        #[test]
+        fn rejects_a_value_without_a_space() {
+            match parse("foo") {
+                Err(Error(ErrorKind::InvalidArgument(..), _)) => (),
+                result => panic!("unexpected result: {:?}", result),
+            }
         }
     }
 }
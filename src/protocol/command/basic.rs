@@ -28,7 +28,7 @@ macro_rules! define_basic_command {
                 #[test]
                 fn correctly_writes_basic_packets() {
                     let packet = $name;
-                    let raw_bytes = packet.bytes();
+                    let raw_bytes = packet.bytes().unwrap();
                     let text = String::from_utf8(raw_bytes).unwrap();
 
                     assert_eq!(text, stringify!($name));
@@ -52,8 +52,6 @@ define_basic_command!(ABOR, abor);
 define_basic_command!(CDUP, cdup);
 // Get the feature list implemented by the server.
 define_basic_command!(FEAT, feat);
-// Extended passive mode.
-define_basic_command!(EPSV, epsv);
 // A no-operation.
 define_basic_command!(NOOP, noop);
 // Enable passive mode.
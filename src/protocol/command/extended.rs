@@ -0,0 +1,225 @@
+//! Commands for data connections over IPv6.
+//!
+//! * [RFC 2428](https://tools.ietf.org/html/rfc2428)
+
+use {Command, Error, ErrorKind};
+
+use std::net::{IpAddr, SocketAddr};
+use std::io::prelude::*;
+
+/// A network protocol, numbered as in RFC 2428.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetworkProtocol
+{
+    /// Internet Protocol, version 4 ('1').
+    Ipv4,
+    /// Internet Protocol, version 6 ('2').
+    Ipv6,
+}
+
+/// Sets up an extended port.
+///
+/// For example `EPRT |2|1080::8:800:200C:417A|5282|`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EPRT
+{
+    /// The network protocol of the address.
+    pub network_protocol: NetworkProtocol,
+    /// The address of the host.
+    pub address: IpAddr,
+    /// The port number.
+    pub port: u16,
+}
+
+/// Enters extended passive mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EPSV
+{
+    /// Listen using the same network protocol as the control connection ('EPSV').
+    Any,
+    /// Listen using a specific network protocol ('EPSV 2').
+    Protocol(NetworkProtocol),
+    /// The client will only use 'EPSV' to set up data connections
+    /// from now on ('EPSV ALL').
+    All,
+}
+
+impl NetworkProtocol
+{
+    /// Gets the network protocol an address belongs to.
+    pub fn of(address: &IpAddr) -> Self {
+        match *address {
+            IpAddr::V4(..) => NetworkProtocol::Ipv4,
+            IpAddr::V6(..) => NetworkProtocol::Ipv6,
+        }
+    }
+
+    /// Gets the number used for the protocol in commands.
+    pub fn number(&self) -> u8 {
+        match *self {
+            NetworkProtocol::Ipv4 => 1,
+            NetworkProtocol::Ipv6 => 2,
+        }
+    }
+
+    /// Parses a protocol number.
+    fn parse(text: &str) -> Result<Self, Error> {
+        match text.parse::<u16>() {
+            Ok(1) => Ok(NetworkProtocol::Ipv4),
+            Ok(2) => Ok(NetworkProtocol::Ipv6),
+            Ok(number) => Err(ErrorKind::UnsupportedNetworkProtocol(number).into()),
+            Err(..) => Err(ErrorKind::InvalidArgument(
+                format!("network protocol should be a number: '{}'", text)).into()),
+        }
+    }
+}
+
+impl EPRT
+{
+    /// Creates a new 'EPRT' command for a socket address.
+    pub fn new(addr: SocketAddr) -> Self {
+        EPRT {
+            network_protocol: NetworkProtocol::of(&addr.ip()),
+            address: addr.ip(),
+            port: addr.port(),
+        }
+    }
+
+    /// Get the address of the socket described by the command.
+    pub fn to_socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+impl Command for EPRT
+{
+    fn write_payload(&self, write: &mut Write) -> Result<(), Error> {
+        write!(write, "|{}|{}|{}|", self.network_protocol.number(), self.address, self.port)?;
+        Ok(())
+    }
+
+    fn read_payload(read: &mut BufRead) -> Result<Self, Error> {
+        let mut payload = String::new();
+        read.read_to_string(&mut payload)?;
+
+        let invalid = || Error::from_kind(ErrorKind::InvalidArgument(
+            format!("EPRT should look like '|1|127.0.0.1|21|': '{}'", payload.trim())));
+
+        // The client can pick any character as the delimiter.
+        let payload = payload.trim();
+        let delimiter = payload.chars().next().ok_or_else(&invalid)?;
+
+        let fields: Vec<&str> = payload.split(delimiter).collect();
+        if fields.len() != 5 || !fields[0].is_empty() || !fields[4].is_empty() {
+            return Err(invalid());
+        }
+
+        let network_protocol = NetworkProtocol::parse(fields[1])?;
+        let address: IpAddr = fields[2].parse().map_err(|_| invalid())?;
+        let port = fields[3].parse().map_err(|_| invalid())?;
+
+        if NetworkProtocol::of(&address) != network_protocol {
+            return Err(ErrorKind::InvalidArgument(
+                format!("{} is not an address for network protocol {}",
+                        address, network_protocol.number())).into());
+        }
+
+        Ok(EPRT { network_protocol: network_protocol, address: address, port: port })
+    }
+
+    fn command_name(&self) -> &'static str { "EPRT" }
+}
+
+impl Command for EPSV
+{
+    fn write_payload(&self, write: &mut Write) -> Result<(), Error> {
+        match *self {
+            EPSV::Any => (),
+            EPSV::Protocol(protocol) => write!(write, "{}", protocol.number())?,
+            EPSV::All => write!(write, "ALL")?,
+        }
+        Ok(())
+    }
+
+    fn read_payload(read: &mut BufRead) -> Result<Self, Error> {
+        let mut payload = String::new();
+        read.read_to_string(&mut payload)?;
+
+        let payload = payload.trim();
+        if payload.is_empty() {
+            Ok(EPSV::Any)
+        } else if payload.eq_ignore_ascii_case("ALL") {
+            Ok(EPSV::All)
+        } else {
+            Ok(EPSV::Protocol(NetworkProtocol::parse(payload)?))
+        }
+    }
+
+    fn command_name(&self) -> &'static str { "EPSV" }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {Command, CommandKind};
+    use std::io;
+
+    fn read(text: &str) -> Result<CommandKind, Error> {
+        CommandKind::read(&mut io::Cursor::new(text))
+    }
+
+    #[test]
+    fn correctly_reads_ipv4_ports() {
+        assert_eq!(read("EPRT |1|132.235.1.2|6275|\r\n").unwrap(), CommandKind::EPRT(EPRT {
+            network_protocol: NetworkProtocol::Ipv4,
+            address: "132.235.1.2".parse().unwrap(),
+            port: 6275,
+        }));
+    }
+
+    #[test]
+    fn correctly_reads_ipv6_ports_with_other_delimiters() {
+        assert_eq!(read("EPRT !2!1080::8:800:200C:417A!5282!\r\n").unwrap(), CommandKind::EPRT(EPRT {
+            network_protocol: NetworkProtocol::Ipv6,
+            address: "1080::8:800:200C:417A".parse().unwrap(),
+            port: 5282,
+        }));
+    }
+
+    #[test]
+    fn correctly_writes_ports() {
+        let command = EPRT::new("[::1]:21".parse().unwrap());
+        assert_eq!(command.to_string().unwrap(), "EPRT |2|::1|21|");
+    }
+
+    #[test]
+    fn rejects_malformed_ports() {
+        assert!(read("EPRT |1|132.235.1.2|\r\n").is_err());
+        assert!(read("EPRT |1|::1|21|\r\n").is_err());
+        assert!(read("EPRT |1|132.235.1.2|99999|\r\n").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_network_protocols() {
+        match read("EPRT |3|132.235.1.2|21|\r\n") {
+            Err(Error(ErrorKind::UnsupportedNetworkProtocol(3), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn correctly_reads_passive_arguments() {
+        assert_eq!(read("EPSV\r\n").unwrap(), CommandKind::EPSV(EPSV::Any));
+        assert_eq!(read("EPSV 2\r\n").unwrap(), CommandKind::EPSV(EPSV::Protocol(NetworkProtocol::Ipv6)));
+        assert_eq!(read("EPSV all\r\n").unwrap(), CommandKind::EPSV(EPSV::All));
+        assert!(read("EPSV 3\r\n").is_err());
+    }
+
+    #[test]
+    fn correctly_writes_passive_arguments() {
+        assert_eq!(EPSV::Any.to_string().unwrap(), "EPSV");
+        assert_eq!(EPSV::Protocol(NetworkProtocol::Ipv4).to_string().unwrap(), "EPSV 1");
+        assert_eq!(EPSV::All.to_string().unwrap(), "EPSV ALL");
+    }
+}
@@ -40,10 +40,22 @@ define_command!(MKD {
     remote_filename: String,
 });
 
+define_command!(MLSD {
+    remote_directory: Option<String>,
+});
+
+define_command!(MLST {
+    remote_filespec: Option<String>,
+});
+
 define_command!(NLST {
     remote_directory: Option<String>,
 });
 
+define_command!(REST {
+    marker: u64,
+});
+
 define_command!(RETR {
     remote_filename: String,
 });
@@ -57,7 +69,7 @@ define_command!(RNFR {
 });
 
 define_command!(RNTO {
-    from_filename: String,
+    to_filename: String,
 });
 
 define_command!(SITE {
@@ -79,3 +91,60 @@ define_command!(STOR {
 define_command!(TYPE {
     file_type: FileType,
 });
+
+define_command!(XRMD {
+    remote_filename: String,
+});
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {Command, CommandKind};
+    use std::io;
+
+    fn read(text: &str) -> CommandKind {
+        CommandKind::read(&mut io::Cursor::new(text)).unwrap()
+    }
+
+    #[test]
+    fn correctly_reads_rnto() {
+        assert_eq!(read("RNTO new name.txt\r\n"),
+                   CommandKind::RNTO(RNTO { to_filename: "new name.txt".to_owned() }));
+    }
+
+    #[test]
+    fn correctly_writes_rnto() {
+        let command = RNTO { to_filename: "new.txt".to_owned() };
+        assert_eq!(command.to_string().unwrap(), "RNTO new.txt");
+    }
+
+    #[test]
+    fn correctly_reads_mlsd() {
+        assert_eq!(read("MLSD\r\n"), CommandKind::MLSD(MLSD { remote_directory: None }));
+        assert_eq!(read("MLSD foo\r\n"),
+                   CommandKind::MLSD(MLSD { remote_directory: Some("foo".to_owned()) }));
+    }
+
+    #[test]
+    fn correctly_reads_mlst() {
+        assert_eq!(read("MLST foo.txt\r\n"),
+                   CommandKind::MLST(MLST { remote_filespec: Some("foo.txt".to_owned()) }));
+    }
+
+    #[test]
+    fn correctly_reads_rest() {
+        assert_eq!(read("REST 1024\r\n"), CommandKind::REST(REST { marker: 1024 }));
+    }
+
+    #[test]
+    fn correctly_writes_rest() {
+        assert_eq!(REST { marker: 1024 }.to_string().unwrap(), "REST 1024");
+    }
+
+    #[test]
+    fn correctly_reads_xrmd() {
+        assert_eq!(read("XRMD foo\r\n"),
+                   CommandKind::XRMD(XRMD { remote_filename: "foo".to_owned() }));
+    }
+}
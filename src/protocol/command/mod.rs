@@ -1,17 +1,22 @@
 pub use self::port::PORT;
+pub use self::extended::{EPRT, EPSV, NetworkProtocol};
 pub use self::mode::{MODE, Mode};
-pub use self::basic::{ABOR, CDUP, EPSV, FEAT, NOOP, PASV, PWD,
+pub use self::opts::OPTS;
+pub use self::basic::{ABOR, CDUP, FEAT, NOOP, PASV, PWD,
                       QUIT, REIN, STOU, SYST};
-pub use self::misc::{ACCT, APPE, CWD, DELE, HELP, LIST, MDTM, MKD, NLST,
-                     RETR, RMD, RNFR, RNTO, SITE, SIZE, STAT, STOR, TYPE,
-                     USER, PASS};
-pub use self::security::{ADAT, AUTH, CCC, CONF, ENC, MIC, PBSZ, PROT};
+pub use self::misc::{ACCT, APPE, CWD, DELE, HELP, LIST, MDTM, MKD, MLSD,
+                     MLST, NLST, REST, RETR, RMD, RNFR, RNTO, SITE, SIZE, STAT,
+                     STOR, TYPE, USER, PASS, XRMD};
+pub use self::security::{ADAT, AUTH, CCC, CONF, ENC, MIC, PBSZ, PROT,
+                         ProtectionLevel};
 pub use self::unimplemented::*;
 
 #[macro_use]
 pub mod macros;
 pub mod port;
+pub mod extended;
 pub mod mode;
+pub mod opts;
 /// Commands which take no arguments.
 pub mod basic;
 pub mod misc;
@@ -52,14 +57,18 @@ pub trait Command : Clone + fmt::Debug + PartialEq + Eq
     /// Gets the name of the command.
     fn command_name(&self) -> &'static str;
 
-    fn bytes(&self) -> Vec<u8> {
+    /// Writes the command to a new buffer.
+    ///
+    /// Fails if an argument can't be sent, such as a path
+    /// containing a line break.
+    fn bytes(&self) -> Result<Vec<u8>, Error> {
         let mut buffer = io::Cursor::new(Vec::new());
-        self.write(&mut buffer).expect("IO failure while writing to memory buffer");
-        buffer.into_inner()
+        self.write(&mut buffer)?;
+        Ok(buffer.into_inner())
     }
 
     /// Generates the text string for this packet.
-    fn to_string(&self) -> String {
-        String::from_utf8(self.bytes()).unwrap()
+    fn to_string(&self) -> Result<String, Error> {
+        Ok(String::from_utf8(self.bytes()?)?)
     }
 }
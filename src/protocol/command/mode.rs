@@ -1,9 +1,8 @@
-use {Argument, Error};
+use {Argument, Error, ErrorKind};
+use argument::read_char;
 
 use std::io::prelude::*;
 
-use byteorder::ReadBytesExt;
-
 define_command!(MODE {
     mode: Mode,
 });
@@ -22,13 +21,13 @@ pub enum Mode
 impl Argument for Mode
 {
     fn read(read: &mut BufRead) -> Result<Self, Error> {
-        let c = read.read_u8()? as char;
-
-        match c {
-            'S' => Ok(Mode::Stream),
-            'B' => Ok(Mode::Block),
-            'C' => Ok(Mode::Compressed),
-            _ => panic!("unknown argument code: {}", c),
+        match read_char(read)?.map(|c| c.to_ascii_uppercase()) {
+            Some('S') => Ok(Mode::Stream),
+            Some('B') => Ok(Mode::Block),
+            Some('C') => Ok(Mode::Compressed),
+            Some(c) => Err(ErrorKind::UnsupportedArgument(
+                format!("unknown transfer mode: '{}'", c.escape_default())).into()),
+            None => Err(ErrorKind::InvalidArgument("missing transfer mode".to_owned()).into()),
         }
     }
 
@@ -64,19 +63,19 @@ mod test
     #[test]
     fn correctly_writes_stream_modeset() {
         let command = MODE { mode: Mode::Stream };
-        assert_eq!(command.to_string(), "MODE S");
+        assert_eq!(command.to_string().unwrap(), "MODE S");
     }
 
     #[test]
     fn correctly_writes_block_modeset() {
         let command = MODE { mode: Mode::Block };
-        assert_eq!(command.to_string(), "MODE B");
+        assert_eq!(command.to_string().unwrap(), "MODE B");
     }
 
     #[test]
     fn correctly_writes_compressed_modeset() {
         let command = MODE { mode: Mode::Compressed };
-        assert_eq!(command.to_string(), "MODE C");
+        assert_eq!(command.to_string().unwrap(), "MODE C");
     }
 
     #[test]
@@ -93,5 +92,23 @@ mod test
     fn correctly_reads_compressed_modeset() {
         assert_eq!(read("MODE C\r\n"), MODE { mode: Mode::Compressed });
     }
+
+    #[test]
+    fn correctly_reads_lowercase_modesets() {
+        assert_eq!(read("mode s\r\n"), MODE { mode: Mode::Stream });
+    }
+
+    #[test]
+    fn rejects_unknown_modes() {
+        match CommandKind::read(&mut io::Cursor::new("MODE X\r\n")) {
+            Err(Error(ErrorKind::UnsupportedArgument(..), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        match CommandKind::read(&mut io::Cursor::new("MODE\r\n")) {
+            Err(Error(ErrorKind::InvalidArgument(..), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
 }
 
@@ -0,0 +1,79 @@
+use {Command, Error, ErrorKind};
+
+use std::io::prelude::*;
+
+/// Sets options for a command.
+///
+/// For example `OPTS MLST type;size;`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OPTS
+{
+    /// The name of the command the options are for.
+    pub command_name: String,
+    /// The options, if any were given.
+    pub options: Option<String>,
+}
+
+impl Command for OPTS
+{
+    fn write_payload(&self, write: &mut Write) -> Result<(), Error> {
+        write!(write, "{}", self.command_name)?;
+
+        if let Some(ref options) = self.options {
+            write!(write, " {}", options)?;
+        }
+        Ok(())
+    }
+
+    fn read_payload(read: &mut BufRead) -> Result<Self, Error> {
+        let mut payload = String::new();
+        read.read_to_string(&mut payload)?;
+
+        let payload = payload.trim();
+        if payload.is_empty() {
+            return Err(ErrorKind::InvalidArgument("OPTS needs a command name".to_owned()).into());
+        }
+
+        let mut parts = payload.splitn(2, ' ');
+        let command_name = parts.next().unwrap().to_owned();
+        let options = parts.next().map(|options| options.trim().to_owned());
+
+        Ok(OPTS { command_name: command_name, options: options })
+    }
+
+    fn command_name(&self) -> &'static str { "OPTS" }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {Command, CommandKind};
+    use std::io;
+
+    fn read(text: &str) -> CommandKind {
+        CommandKind::read(&mut io::Cursor::new(text)).unwrap()
+    }
+
+    #[test]
+    fn correctly_reads_options() {
+        assert_eq!(read("OPTS MLST type;size;\r\n"), CommandKind::OPTS(OPTS {
+            command_name: "MLST".to_owned(),
+            options: Some("type;size;".to_owned()),
+        }));
+    }
+
+    #[test]
+    fn correctly_reads_empty_options() {
+        assert_eq!(read("OPTS MLST\r\n"), CommandKind::OPTS(OPTS {
+            command_name: "MLST".to_owned(),
+            options: None,
+        }));
+    }
+
+    #[test]
+    fn correctly_writes_options() {
+        let command = OPTS { command_name: "MLST".to_owned(), options: Some("type;".to_owned()) };
+        assert_eq!(command.to_string().unwrap(), "OPTS MLST type;");
+    }
+}
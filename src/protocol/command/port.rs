@@ -42,16 +42,21 @@ impl Command for PORT
         let mut payload = String::new();
         read.read_to_string(&mut payload)?;
 
-        assert_eq!(payload.chars().next(), Some(' '), "missing space after command");
+        if !payload.starts_with(' ') {
+            return Err(ErrorKind::InvalidArgument("PORT needs an address".to_owned()).into());
+        }
         let payload: String = payload.chars().skip(1).collect();
 
         let textual_bytes: Vec<&str> = payload.split(",").collect();
-        assert_eq!(textual_bytes.len(), 6, "there should be 6 bytes in a PORT payload");
+        if textual_bytes.len() != 6 {
+            return Err(ErrorKind::InvalidArgument(
+                format!("there should be 6 bytes in a PORT payload, got {}", textual_bytes.len())).into());
+        }
 
         let bytes: Result<Vec<u8>, _> = textual_bytes.into_iter().map(|tb| match tb.parse() {
             Ok(b) => Ok(b),
             Err(..) => Err(Error::from_kind(ErrorKind::InvalidArgument(
-                format!("PORT addresses should be comma-separated integers")))),
+                "PORT addresses should be comma-separated integers".to_owned()))),
         }).collect();
         let bytes = bytes?;
 
@@ -74,7 +79,7 @@ mod test
     #[test]
     fn correctly_writes_basic_packets() {
         let packet = PORT { host_address: [127,0,0,1], port: 22 };
-        let raw_bytes = packet.bytes();
+        let raw_bytes = packet.bytes().unwrap();
         let text = String::from_utf8(raw_bytes).unwrap();
 
         assert_eq!(text, "PORT 127,0,0,1,0,22");
@@ -87,5 +92,15 @@ mod test
 
         assert_eq!(command, CommandKind::PORT(PORT { host_address: [192,168,1,1], port: 65535 }));
     }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        for text in &["PORT\r\n", "PORT 1,2,3\r\n", "PORT 1,2,3,4,5,6,7\r\n", "PORT 1,2,3,4,5,256\r\n"] {
+            match CommandKind::read(&mut io::Cursor::new(text.as_bytes().to_vec())) {
+                Err(Error(ErrorKind::InvalidArgument(..), _)) => (),
+                result => panic!("unexpected result for {:?}: {:?}", text, result),
+            }
+        }
+    }
 }
 
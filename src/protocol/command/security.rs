@@ -1,6 +1,12 @@
 //! FTP security extensions from RFC 2228.
 //!
 //! * [RFC 2228](https://tools.ietf.org/html/rfc2228)
+//! * [RFC 4217](https://tools.ietf.org/html/rfc4217)
+
+use {Argument, Error, ErrorKind};
+use argument::read_char;
+
+use std::io::prelude::*;
 
 define_command!(AUTH {
     auth_type: String,
@@ -14,13 +20,8 @@ define_command!(PBSZ {
     protection_buffer_size: u32,
 });
 
-// FIXME: Turn protection_level into an enum.
-// C - Clear
-// S - Safe
-// E - Confidential
-// P - Private
 define_command!(PROT {
-    protection_level: String,
+    protection_level: ProtectionLevel,
 });
 
 define_command!(CCC { });
@@ -36,3 +37,86 @@ define_command!(CONF {
 define_command!(ENC {
     message: String,
 });
+
+/// How data connections are protected.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ProtectionLevel
+{
+    /// No protection, level character 'C'.
+    #[default]
+    Clear,
+    /// Integrity protection, level character 'S'.
+    Safe,
+    /// Confidentiality protection, level character 'E'.
+    Confidential,
+    /// Integrity and confidentiality protection, level character 'P'.
+    Private,
+}
+
+impl Argument for ProtectionLevel
+{
+    fn read(read: &mut BufRead) -> Result<Self, Error> {
+        let c = match read_char(read)? {
+            Some(c) => c,
+            None => return Err(ErrorKind::InvalidArgument("missing protection level".to_owned()).into()),
+        };
+
+        match c.to_ascii_uppercase() {
+            'C' => Ok(ProtectionLevel::Clear),
+            'S' => Ok(ProtectionLevel::Safe),
+            'E' => Ok(ProtectionLevel::Confidential),
+            'P' => Ok(ProtectionLevel::Private),
+            _ => Err(ErrorKind::UnsupportedArgument(
+                format!("unknown protection level: '{}'", c.escape_default())).into()),
+        }
+    }
+
+    fn write(&self, write: &mut Write) -> Result<(), Error> {
+        let level_character = match *self {
+            ProtectionLevel::Clear => 'C',
+            ProtectionLevel::Safe => 'S',
+            ProtectionLevel::Confidential => 'E',
+            ProtectionLevel::Private => 'P',
+        };
+
+        write.write_all(&[level_character as u8])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use {Command, CommandKind};
+    use std::io;
+
+    fn read(text: &str) -> Result<CommandKind, Error> {
+        CommandKind::read(&mut io::Cursor::new(text))
+    }
+
+    #[test]
+    fn correctly_reads_auth() {
+        assert_eq!(read("AUTH TLS\r\n").unwrap(),
+                   CommandKind::AUTH(AUTH { auth_type: "TLS".to_owned() }));
+    }
+
+    #[test]
+    fn correctly_reads_protection_levels() {
+        assert_eq!(read("PROT P\r\n").unwrap(),
+                   CommandKind::PROT(PROT { protection_level: ProtectionLevel::Private }));
+        assert_eq!(read("PROT c\r\n").unwrap(),
+                   CommandKind::PROT(PROT { protection_level: ProtectionLevel::Clear }));
+    }
+
+    #[test]
+    fn rejects_unknown_protection_levels() {
+        assert!(read("PROT X\r\n").is_err());
+    }
+
+    #[test]
+    fn correctly_writes_protection_levels() {
+        let command = PROT { protection_level: ProtectionLevel::Private };
+        assert_eq!(command.to_string().unwrap(), "PROT P");
+    }
+}
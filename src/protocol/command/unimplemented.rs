@@ -8,12 +8,14 @@ macro_rules! define_unimplemented_command
         {
             fn write_payload(&self, _: &mut ::std::io::Write)
                 -> Result<(), $crate::Error> {
-                unimplemented!();
+                Ok(())
             }
 
+            // The arguments are ignored, it is up to the server to
+            // tell the client the command isn't implemented.
             fn read_payload(_: &mut ::std::io::BufRead)
                 -> Result<Self, $crate::Error> {
-                panic!("received unimplemented command: {}", stringify!($name));
+                Ok($name)
             }
 
             fn command_name(&self) -> &'static str { stringify!($name) }
@@ -22,22 +24,16 @@ macro_rules! define_unimplemented_command
 }
 
 define_unimplemented_command!(ALLO);
-define_unimplemented_command!(EPRT);
 define_unimplemented_command!(HOST);
 define_unimplemented_command!(LANG);
 define_unimplemented_command!(LPRT);
 define_unimplemented_command!(LPSV);
-define_unimplemented_command!(MLSD);
-define_unimplemented_command!(MLST);
-define_unimplemented_command!(OPTS);
-define_unimplemented_command!(REST);
 define_unimplemented_command!(SMNT);
 define_unimplemented_command!(STRU);
 define_unimplemented_command!(XCUP);
 define_unimplemented_command!(XMKD);
 define_unimplemented_command!(XPWD);
 define_unimplemented_command!(XRCP);
-define_unimplemented_command!(XRMD);
 define_unimplemented_command!(XRSQ);
 define_unimplemented_command!(XSEM);
 define_unimplemented_command!(XSEN);
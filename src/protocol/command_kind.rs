@@ -135,28 +135,60 @@ impl CommandKind
 {
     /// Reads a command from a buffer.
     pub fn read(read: &mut Read) -> Result<Self, Error> {
-        let line_bytes: Result<Vec<u8>, _> = read.bytes().take_while(|b| b.as_ref().map(|&b| (b as char) != '\n').unwrap_or(true)).collect();
-        let mut line_bytes = line_bytes?;
+        let mut line_bytes = Vec::new();
+        let mut found_end_of_line = false;
+        for byte in read.bytes() {
+            match byte? {
+                b'\n' => { found_end_of_line = true; break },
+                byte => line_bytes.push(byte),
+            }
+        }
+
+        if !found_end_of_line {
+            return Err(ErrorKind::SyntaxError(
+                "missing end of line".to_owned(), line_bytes.len()).into());
+        }
+
+        // New lines should use '\r\n', but we also accept a bare '\n'.
+        if line_bytes.last() == Some(&b'\r') {
+            line_bytes.pop();
+        }
 
-        // Every new line should use '\r\n', and we trimmed the '\n' above.
-        assert_eq!(line_bytes.last(), Some(&('\r' as u8)), "new lines should be CR+LF, not just LF");
-        line_bytes.pop();
+        let line_string = match String::from_utf8(line_bytes) {
+            Ok(line_string) => line_string,
+            Err(e) => {
+                let position = e.utf8_error().valid_up_to();
+                return Err(ErrorKind::SyntaxError("invalid UTF-8".to_owned(), position).into());
+            },
+        };
 
-        let line_string = String::from_utf8(line_bytes)?;
+        // Split the line up, ignoring extra spaces around the command name.
+        let line = line_string.trim_left_matches(' ');
+        let (command_name, payload) = match line.find(' ') {
+            Some(position) => {
+                let (command_name, rest) = line.split_at(position);
+                let argument = rest.trim_left_matches(' ');
 
-        // Split the line up.
-        let (command_name, payload) = if line_string.contains(' ') {
-            line_string.split_at(line_string.chars().position(|c| c == ' ').expect("no space in line"))
-        } else {
+                // Arguments are read with exactly one leading space.
+                let payload = if argument.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", argument)
+                };
+                (command_name, payload)
+            },
             // If the line has no space, it has no payload.
-            (line_string.as_str(), "")
+            None => (line, String::new()),
         };
 
+        // Command names are case-insensitive.
+        let command_name = command_name.to_ascii_uppercase();
+
         let mut payload_reader = io::BufReader::new(io::Cursor::new(payload));
 
         macro_rules! read_commands {
             ( $cmd_name:ident => $( $name:ident ),+ ) => {
-                match command_name {
+                match command_name.as_str() {
                     $( stringify!($name) => Ok(CommandKind::$name($name::read_payload(&mut payload_reader)?)), )+
                     _ => Err(ErrorKind::InvalidCommand(command_name.to_owned()).into()),
                 }
@@ -192,3 +224,131 @@ impl CommandKind
         )
     }
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use reply::{code, AsReplyCode};
+    use std::io;
+
+    fn read(bytes: &[u8]) -> Result<CommandKind, Error> {
+        CommandKind::read(&mut io::Cursor::new(bytes))
+    }
+
+    /// A small pseudo-random number generator so the tests are repeatable.
+    struct Lcg(u64);
+
+    impl Lcg
+    {
+        fn next(&mut self) -> u8 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) as u8
+        }
+    }
+
+    #[test]
+    fn rejects_lines_without_an_end() {
+        match read(b"NOOP") {
+            Err(Error(ErrorKind::SyntaxError(_, 4), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn accepts_bare_line_feeds() {
+        assert_eq!(read(b"NOOP\n").unwrap(), CommandKind::NOOP(NOOP));
+        assert_eq!(read(b"USER bob\n").unwrap(),
+                   CommandKind::USER(USER { username: "bob".to_owned() }));
+    }
+
+    #[test]
+    fn reads_command_names_in_any_case() {
+        assert_eq!(read(b"user bob\r\n").unwrap(),
+                   CommandKind::USER(USER { username: "bob".to_owned() }));
+        assert_eq!(read(b"Pwd\r\n").unwrap(), CommandKind::PWD(PWD));
+    }
+
+    #[test]
+    fn ignores_extra_spaces() {
+        assert_eq!(read(b"  USER   bob\r\n").unwrap(),
+                   CommandKind::USER(USER { username: "bob".to_owned() }));
+        assert_eq!(read(b"PWD \r\n").unwrap(), CommandKind::PWD(PWD));
+        assert_eq!(read(b"LIST  \r\n").unwrap(),
+                   CommandKind::LIST(LIST { remote_filespec: None }));
+    }
+
+    #[test]
+    fn keeps_spaces_inside_arguments() {
+        assert_eq!(read(b"RETR my file.txt\r\n").unwrap(),
+                   CommandKind::RETR(RETR { remote_filename: "my file.txt".to_owned() }));
+    }
+
+    #[test]
+    fn reports_where_invalid_utf8_is() {
+        match read(b"USER b\xffb\r\n") {
+            Err(Error(ErrorKind::SyntaxError(_, 6), _)) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let error = read(b"FOO bar\r\n").unwrap_err();
+        assert_eq!(error.as_reply_code(), code::INVALID_COMMAND);
+    }
+
+    #[test]
+    fn maps_argument_errors_to_reply_codes() {
+        assert_eq!(read(b"REST abc\r\n").unwrap_err().as_reply_code(), code::SYNTAX_ERROR);
+        assert_eq!(read(b"TYPE X\r\n").unwrap_err().as_reply_code(),
+                   code::COMMAND_NOT_IMPLEMENTED_FOR_PARAMETER);
+    }
+
+    #[test]
+    fn reads_unimplemented_commands() {
+        assert_eq!(read(b"ALLO 1024\r\n").unwrap(), CommandKind::ALLO(ALLO));
+        assert_eq!(read(b"SMNT\r\n").unwrap(), CommandKind::SMNT(SMNT));
+    }
+
+    #[test]
+    fn never_panics_on_arbitrary_bytes() {
+        const NAMES: &'static [&'static str] = &[
+            "ABOR", "ACCT", "ADAT", "ALLO", "APPE", "AUTH", "CCC", "CDUP", "CONF", "CWD",
+            "DELE", "ENC", "EPRT", "EPSV", "FEAT", "HELP", "HOST", "LANG", "LIST", "LPRT",
+            "LPSV", "MDTM", "MIC", "MKD", "MLSD", "MLST", "MODE", "NLST", "NOOP", "OPTS",
+            "PASS", "PASV", "PBSZ", "PORT", "PROT", "PWD", "QUIT", "REIN", "REST", "RETR",
+            "RMD", "RNFR", "RNTO", "SITE", "SIZE", "SMNT", "STAT", "STOR", "STOU", "STRU",
+            "SYST", "TYPE", "USER", "XCUP", "XMKD", "XPWD", "XRCP", "XRMD", "XRSQ", "XSEM",
+            "XSEN",
+        ];
+        // Bytes that are most likely to confuse the argument parsers.
+        const INTERESTING: &'static [u8] = b" ,|\r\n0123456789AEILNPSTCBx\xff";
+
+        let mut random = Lcg(0x5eed);
+
+        for _ in 0..5000 {
+            let mut line = Vec::new();
+            if random.next() % 4 != 0 {
+                let name = NAMES[random.next() as usize % NAMES.len()];
+                line.extend_from_slice(name.as_bytes());
+            }
+
+            let length = random.next() % 16;
+            for _ in 0..length {
+                let byte = if random.next() % 2 == 0 {
+                    INTERESTING[random.next() as usize % INTERESTING.len()]
+                } else {
+                    random.next()
+                };
+                line.push(byte);
+            }
+
+            if random.next() % 4 != 0 {
+                line.extend_from_slice(b"\r\n");
+            }
+
+            let _ = read(&line);
+        }
+    }
+}
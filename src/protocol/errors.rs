@@ -24,11 +24,31 @@ error_chain! {
             display("received invalid argument: {}", message)
         }
 
+        UnsupportedArgument(message: String) {
+            description("received unsupported argument")
+            display("received unsupported argument: {}", message)
+        }
+
+        SyntaxError(message: String, position: usize) {
+            description("received malformed command line")
+            display("received malformed command line at byte {}: {}", position, message)
+        }
+
         InvalidCommandSequence(message: String) {
             description("received invalid command sequence")
             display("received invalid command sequence: {}", message)
         }
 
+        UnsupportedNetworkProtocol(number: u16) {
+            description("received unsupported network protocol")
+            display("network protocol {} is not supported, use (1,2)", number)
+        }
+
+        InvalidReply(message: String) {
+            description("received invalid reply")
+            display("received invalid reply: {}", message)
+        }
+
         UnimplementedCommand(name: String) {
             description("received command that is not implemented yet")
             display("received command that is not implemented yet: '{}'", name)
@@ -0,0 +1,300 @@
+//! Machine-readable facts about files, as sent by 'MLST' and 'MLSD'.
+//!
+//! * [RFC 3659 section 7](https://tools.ietf.org/html/rfc3659#section-7)
+
+use {Error, ErrorKind};
+use time::DateTime;
+
+use std::time::SystemTime;
+
+/// A kind of fact that can be given about a file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FactKind
+{
+    /// The kind of entry ('type').
+    Type,
+    /// The size in bytes ('size').
+    Size,
+    /// The last modification time ('modify').
+    Modify,
+    /// What the current user is allowed to do with the entry ('perm').
+    Perm,
+    /// An identifier that is unique to the entry ('unique').
+    Unique,
+    /// The Unix permission bits ('UNIX.mode').
+    UnixMode,
+    /// The owner of the entry ('UNIX.owner').
+    UnixOwner,
+    /// The group of the entry ('UNIX.group').
+    UnixGroup,
+}
+
+/// The kind of entry, given by the 'type' fact.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntryType
+{
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// The directory being listed.
+    CurrentDir,
+    /// The parent of the directory being listed.
+    ParentDir,
+    /// Any other type, such as 'OS.unix=symlink'.
+    Other(String),
+}
+
+/// Facts about a single file.
+///
+/// Facts which are not known are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Facts
+{
+    pub entry_type: Option<EntryType>,
+    pub size: Option<u64>,
+    pub modify: Option<SystemTime>,
+    pub perm: Option<String>,
+    pub unique: Option<String>,
+    pub unix_mode: Option<u32>,
+    pub unix_owner: Option<String>,
+    pub unix_group: Option<String>,
+}
+
+/// A file along with facts about it.
+///
+/// This is a single line of 'MLSD' output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry
+{
+    pub facts: Facts,
+    /// The path or name of the file.
+    pub name: String,
+}
+
+const ALL_FACT_KINDS: [FactKind; 8] = [
+    FactKind::Type, FactKind::Size, FactKind::Modify, FactKind::Perm,
+    FactKind::Unique, FactKind::UnixMode, FactKind::UnixOwner, FactKind::UnixGroup,
+];
+
+impl FactKind
+{
+    /// Gets every kind of fact we know about.
+    pub fn all() -> &'static [FactKind] { &ALL_FACT_KINDS }
+
+    /// Gets the name of the fact.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FactKind::Type => "type",
+            FactKind::Size => "size",
+            FactKind::Modify => "modify",
+            FactKind::Perm => "perm",
+            FactKind::Unique => "unique",
+            FactKind::UnixMode => "UNIX.mode",
+            FactKind::UnixOwner => "UNIX.owner",
+            FactKind::UnixGroup => "UNIX.group",
+        }
+    }
+
+    /// Looks up a fact by its name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALL_FACT_KINDS.iter().cloned().find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Parses the list of facts given to 'OPTS MLST', such as `type;size;`.
+    ///
+    /// Unknown facts are ignored as required by the RFC.
+    pub fn parse_list(text: &str) -> Vec<Self> {
+        text.split(';').filter_map(|name| FactKind::from_name(name.trim())).collect()
+    }
+
+    /// Writes a list of facts such as `type;size;`.
+    pub fn list_to_string(kinds: &[FactKind]) -> String {
+        kinds.iter().map(|kind| format!("{};", kind.name())).collect()
+    }
+
+    /// Generates the 'MLST' line given in the 'FEAT' response.
+    ///
+    /// Facts that are currently enabled are marked with an asterisk.
+    pub fn feature_string(enabled: &[FactKind]) -> String {
+        let facts: String = ALL_FACT_KINDS.iter().map(|kind| {
+            let marker = if enabled.contains(kind) { "*" } else { "" };
+            format!("{}{};", kind.name(), marker)
+        }).collect();
+
+        format!("MLST {}", facts)
+    }
+}
+
+impl EntryType
+{
+    /// Gets the value of the 'type' fact.
+    pub fn name(&self) -> &str {
+        match *self {
+            EntryType::File => "file",
+            EntryType::Dir => "dir",
+            EntryType::CurrentDir => "cdir",
+            EntryType::ParentDir => "pdir",
+            EntryType::Other(ref name) => name,
+        }
+    }
+
+    fn from_name(name: &str) -> Self {
+        match &name.to_ascii_lowercase()[..] {
+            "file" => EntryType::File,
+            "dir" => EntryType::Dir,
+            "cdir" => EntryType::CurrentDir,
+            "pdir" => EntryType::ParentDir,
+            _ => EntryType::Other(name.to_owned()),
+        }
+    }
+}
+
+impl Facts
+{
+    /// Gets the value of a fact as text, if it is known.
+    fn value(&self, kind: FactKind) -> Option<String> {
+        match kind {
+            FactKind::Type => self.entry_type.as_ref().map(|t| t.name().to_owned()),
+            FactKind::Size => self.size.map(|size| size.to_string()),
+            FactKind::Modify => self.modify.map(|time| DateTime::from_system_time(time).timeval()),
+            FactKind::Perm => self.perm.clone(),
+            FactKind::Unique => self.unique.clone(),
+            FactKind::UnixMode => self.unix_mode.map(|mode| format!("0{:o}", mode)),
+            FactKind::UnixOwner => self.unix_owner.clone(),
+            FactKind::UnixGroup => self.unix_group.clone(),
+        }
+    }
+
+    /// Sets a fact from its textual value.
+    fn set_value(&mut self, kind: FactKind, value: &str) -> Result<(), Error> {
+        let invalid = || Error::from_kind(ErrorKind::InvalidArgument(
+            format!("invalid value for fact '{}': '{}'", kind.name(), value)));
+
+        match kind {
+            FactKind::Type => self.entry_type = Some(EntryType::from_name(value)),
+            FactKind::Size => self.size = Some(value.parse().map_err(|_| invalid())?),
+            FactKind::Modify => {
+                let time = DateTime::parse_timeval(value).ok_or_else(&invalid)?;
+                self.modify = Some(time.to_system_time());
+            },
+            FactKind::Perm => self.perm = Some(value.to_owned()),
+            FactKind::Unique => self.unique = Some(value.to_owned()),
+            FactKind::UnixMode => {
+                self.unix_mode = Some(u32::from_str_radix(value, 8).map_err(|_| invalid())?);
+            },
+            FactKind::UnixOwner => self.unix_owner = Some(value.to_owned()),
+            FactKind::UnixGroup => self.unix_group = Some(value.to_owned()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Entry
+{
+    /// Formats the entry as a line of 'MLSD' output, without a line ending.
+    ///
+    /// Only the given kinds of facts are written.
+    pub fn to_line(&self, kinds: &[FactKind]) -> String {
+        let mut line = String::new();
+
+        for &kind in kinds {
+            if let Some(value) = self.facts.value(kind) {
+                line.push_str(&format!("{}={};", kind.name(), value));
+            }
+        }
+
+        line.push(' ');
+        line.push_str(&self.name);
+        line
+    }
+
+    /// Parses a line of 'MLSD' output.
+    ///
+    /// Facts we don't know about are skipped.
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let space = match line.find(' ') {
+            Some(space) => space,
+            None => return Err(ErrorKind::InvalidArgument(
+                format!("missing space before file name: '{}'", line)).into()),
+        };
+        let (facts_text, name) = (&line[..space], &line[space + 1..]);
+
+        let mut facts = Facts::default();
+        for fact in facts_text.split(';').filter(|fact| !fact.is_empty()) {
+            let mut parts = fact.splitn(2, '=');
+            let (name, value) = (parts.next().unwrap(), parts.next().unwrap_or(""));
+
+            if let Some(kind) = FactKind::from_name(name) {
+                facts.set_value(kind, value)?;
+            }
+        }
+
+        Ok(Entry { facts: facts, name: name.to_owned() })
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    pub use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn entry() -> Entry {
+        Entry {
+            facts: Facts {
+                entry_type: Some(EntryType::File),
+                size: Some(1024),
+                modify: Some(UNIX_EPOCH + Duration::from_secs(1_500_000_000)),
+                perm: Some("adfrw".to_owned()),
+                unique: None,
+                unix_mode: Some(0o644),
+                unix_owner: Some("ftp".to_owned()),
+                unix_group: None,
+            },
+            name: "foo bar.txt".to_owned(),
+        }
+    }
+
+    #[test]
+    fn correctly_writes_an_entry() {
+        assert_eq!(entry().to_line(FactKind::all()),
+                   "type=file;size=1024;modify=20170714024000;perm=adfrw;UNIX.mode=0644;UNIX.owner=ftp; foo bar.txt");
+    }
+
+    #[test]
+    fn only_writes_the_requested_facts() {
+        assert_eq!(entry().to_line(&[FactKind::Size, FactKind::Type]),
+                   "size=1024;type=file; foo bar.txt");
+        assert_eq!(entry().to_line(&[]), " foo bar.txt");
+    }
+
+    #[test]
+    fn correctly_reads_an_entry() {
+        let line = entry().to_line(FactKind::all());
+        assert_eq!(Entry::parse(&line).unwrap(), entry());
+    }
+
+    #[test]
+    fn ignores_unknown_facts_when_reading() {
+        let entry = Entry::parse("Type=cdir;x.foo=bar;SIZE=3; /").unwrap();
+
+        assert_eq!(entry.facts.entry_type, Some(EntryType::CurrentDir));
+        assert_eq!(entry.facts.size, Some(3));
+        assert_eq!(entry.name, "/");
+    }
+
+    #[test]
+    fn correctly_parses_fact_lists() {
+        assert_eq!(FactKind::parse_list("type;SIZE;bogus;UNIX.mode;"),
+                   vec![FactKind::Type, FactKind::Size, FactKind::UnixMode]);
+        assert_eq!(FactKind::parse_list(""), vec![]);
+    }
+
+    #[test]
+    fn correctly_generates_the_feature_string() {
+        assert_eq!(FactKind::feature_string(&[FactKind::Type, FactKind::Size]),
+                   "MLST type*;size*;modify;perm;unique;UNIX.mode;UNIX.owner;UNIX.group;");
+    }
+}
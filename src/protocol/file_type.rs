@@ -1,6 +1,6 @@
 use {Argument, Error, ErrorKind};
+use argument::read_char;
 
-use byteorder::ReadBytesExt;
 use std::io::prelude::*;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -28,9 +28,12 @@ impl FileType
 impl Argument for FileType
 {
     fn read(read: &mut BufRead) -> Result<Self, Error> {
-        let type_byte = read.read_u8()?;
+        let type_char = match read_char(read)? {
+            Some(c) => c.to_ascii_uppercase(),
+            None => return Err(ErrorKind::InvalidArgument("missing file type".to_owned()).into()),
+        };
 
-        match type_byte as char {
+        match type_char {
             'A' => {
                 let format = TextFormat::read(read)?;
                 Ok(FileType::AsciiText(format))
@@ -41,16 +44,21 @@ impl Argument for FileType
             },
             'I' => Ok(FileType::Binary),
             'L' => {
-                assert_eq!(read.read_u8()? as char, ' ');
-                let bits_per_byte_char = read.read_u8()? as char;
-
-                match bits_per_byte_char.to_string().parse() {
-                    Ok(bits_per_byte) => Ok(FileType::LocalFormat { bits_per_byte: bits_per_byte }),
-                    Err(..) => Err(ErrorKind::InvalidArgument(
-                        format!("file type should be single digit number, got '{}'", bits_per_byte_char)).into()),
+                let bits_per_byte_char = match (read_char(read)?, read_char(read)?) {
+                    (Some(' '), Some(c)) => c,
+                    _ => return Err(ErrorKind::InvalidArgument(
+                        "local file type should be followed by the byte size".to_owned()).into()),
+                };
+
+                match bits_per_byte_char.to_digit(10) {
+                    Some(bits_per_byte) => Ok(FileType::LocalFormat { bits_per_byte: bits_per_byte as u8 }),
+                    None => Err(ErrorKind::InvalidArgument(
+                        format!("file type should be single digit number, got '{}'",
+                                bits_per_byte_char.escape_default())).into()),
                 }
             },
-            c => panic!("invalid file type char: '{}'", c),
+            c => Err(ErrorKind::UnsupportedArgument(
+                format!("unknown file type: '{}'", c.escape_default())).into()),
         }
     }
 
@@ -80,21 +88,17 @@ impl Argument for FileType
 impl Argument for TextFormat
 {
     fn read(read: &mut BufRead) -> Result<Self, Error> {
-        let mut buf: [u8; 1] = [0; 1];
-
         // Check if we received a character.
-        if read.read(&mut buf)? == 1 {
-            assert_eq!(buf[0] as char, ' ');
-
-            match read.read_u8()? as char {
-                'N' => Ok(TextFormat::NonPrint),
-                'T' => Ok(TextFormat::TelnetFormatControl),
-                'C' => Ok(TextFormat::ASACarriageControl),
-                _ => panic!("invalid text format character"),
-            }
-        } else {
+        match (read_char(read)?, read_char(read)?.map(|c| c.to_ascii_uppercase())) {
+            (Some(' '), Some('N')) => Ok(TextFormat::NonPrint),
+            (Some(' '), Some('T')) => Ok(TextFormat::TelnetFormatControl),
+            (Some(' '), Some('C')) => Ok(TextFormat::ASACarriageControl),
+            (Some(' '), Some(c)) => Err(ErrorKind::UnsupportedArgument(
+                format!("unknown text format: '{}'", c.escape_default())).into()),
             // The default is non-print.
-            Ok(TextFormat::NonPrint)
+            (None, _) => Ok(TextFormat::NonPrint),
+            _ => Err(ErrorKind::InvalidArgument(
+                "text format should be separated by a space".to_owned()).into()),
         }
     }
 
@@ -115,27 +119,28 @@ mod test
 {
     use Argument;
     use super::*;
+    use std::io;
 
     #[test]
     fn correctly_writes_ascii_nonprint() {
-        assert_eq!(FileType::AsciiText(TextFormat::NonPrint).to_string(),
+        assert_eq!(FileType::AsciiText(TextFormat::NonPrint).to_string().unwrap(),
                    " A N");
     }
 
     #[test]
     fn correctly_writes_ebcdic_telnet() {
-        assert_eq!(FileType::EbcdicText(TextFormat::TelnetFormatControl).to_string(),
+        assert_eq!(FileType::EbcdicText(TextFormat::TelnetFormatControl).to_string().unwrap(),
                    " E T");
     }
 
     #[test]
     fn correctly_writes_binary() {
-        assert_eq!(FileType::Binary.to_string(), " I");
+        assert_eq!(FileType::Binary.to_string().unwrap(), " I");
     }
 
     #[test]
     fn correctly_writes_local_5bit() {
-        assert_eq!(FileType::LocalFormat { bits_per_byte: 5 }.to_string(), " L 5");
+        assert_eq!(FileType::LocalFormat { bits_per_byte: 5 }.to_string().unwrap(), " L 5");
     }
 
     #[test]
@@ -160,4 +165,31 @@ mod test
         assert_eq!(FileType::parse_text(" L 2"),
                    FileType::LocalFormat { bits_per_byte: 2 });
     }
+
+    #[test]
+    fn correctly_reads_lowercase_types() {
+        assert_eq!(FileType::parse_text(" i"), FileType::Binary);
+        assert_eq!(FileType::parse_text(" a t"),
+                   FileType::AsciiText(TextFormat::TelnetFormatControl));
+    }
+
+    #[test]
+    fn rejects_malformed_types() {
+        for text in &[" ", " L", " L2", " L x", " AN"] {
+            match FileType::read_with_space(&mut io::Cursor::new(text)) {
+                Err(Error(ErrorKind::InvalidArgument(..), _)) => (),
+                result => panic!("unexpected result for {:?}: {:?}", text, result),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_types() {
+        for text in &[" X", " A Z"] {
+            match FileType::read_with_space(&mut io::Cursor::new(text)) {
+                Err(Error(ErrorKind::UnsupportedArgument(..), _)) => (),
+                result => panic!("unexpected result for {:?}: {:?}", text, result),
+            }
+        }
+    }
 }
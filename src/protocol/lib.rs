@@ -1,6 +1,10 @@
 //! Raw FTP protocol definitions.
 //!
 //! * [RFC 959](https://www.w3.org/Protocols/rfc959)
+//! * [RFC 2228](https://tools.ietf.org/html/rfc2228)
+//! * [RFC 2428](https://tools.ietf.org/html/rfc2428)
+//! * [RFC 3659](https://tools.ietf.org/html/rfc3659)
+//! * [RFC 4217](https://tools.ietf.org/html/rfc4217)
 //! * http://www.nsftools.com/tips/RawFTP.htm
 
 pub extern crate rfc1700;
@@ -23,4 +27,6 @@ pub mod reply;
 pub mod command;
 pub mod errors;
 pub mod file_type;
+pub mod facts;
+pub mod time;
 
@@ -52,6 +52,20 @@ pub const REQUESTED_ACTION_ABORTED_PAGE_TYPE_UNKNOWN: Code = Code(551);
 pub const REQUESTED_ACTION_NOT_TAKEN_INSUFFICIENT_STORAGE: Code = Code(452);
 pub const REQUESTED_FILE_ACTION_ABORTED_EXCEEDED_ALLOCATION: Code = Code(552);
 pub const INVALID_FILE_NAME: Code = Code(553);
+/// From RFC 3659.
+pub const INVALID_REST_PARAMETER: Code = Code(554);
+/// From RFC 2428.
+pub const NETWORK_PROTOCOL_NOT_SUPPORTED: Code = Code(522);
+/// From RFC 2228.
+pub const SECURITY_DATA_EXCHANGE_COMPLETE: Code = Code(234);
+/// From RFC 2228.
+pub const NEED_UNAVAILABLE_RESOURCE_TO_PROCESS_SECURITY: Code = Code(431);
+/// From RFC 2228.
+pub const REQUEST_DENIED_FOR_POLICY_REASONS: Code = Code(534);
+/// From RFC 2228.
+pub const PROTECTION_LEVEL_NOT_SUPPORTED: Code = Code(536);
+/// From RFC 4217.
+pub const DATA_CONNECTION_CANNOT_BE_OPENED_WITH_PROT: Code = Code(521);
 
 impl AsReplyCode for ErrorKind {
     fn as_reply_code(&self) -> Code {
@@ -63,10 +77,13 @@ impl AsReplyCode for ErrorKind {
             // invalid command.
             InvalidUtf8(..) => INVALID_COMMAND,
             NotLoggedIn => USER_NOT_LOGGED_IN,
+            SyntaxError(..) => INVALID_COMMAND,
             InvalidArgument(..) => SYNTAX_ERROR,
+            UnsupportedArgument(..) => COMMAND_NOT_IMPLEMENTED_FOR_PARAMETER,
             InvalidCommandSequence(..) => BAD_COMMAND_SEQUENCE,
+            UnsupportedNetworkProtocol(..) => NETWORK_PROTOCOL_NOT_SUPPORTED,
             UnimplementedCommand(..) => COMMAND_NOT_IMPLEMENTED,
-            Msg(..) | Io(..)
+            Msg(..) | Io(..) | InvalidReply(..)
                 => REQUESTED_ACTION_ABORTED_LOCAL_ERROR_IN_PROCESSING,
         }
     }
@@ -21,32 +21,100 @@ macro_rules! define_replies {
 
 // NOTE: Keep commands in ALPHABETICAL ORDER.
 
+define_replies!(abor {
+    success() => CLOSING_DATA_CONNECTION @ "transfer aborted successfully",
+    no_transfer() => DATA_CONNECTION_OPEN_NO_TRANSFER_IN_PROGRESS @ "no transfer to abort"
+});
+
+define_replies!(auth {
+    success() => SECURITY_DATA_EXCHANGE_COMPLETE @ "starting TLS negotiation",
+    unavailable() => NEED_UNAVAILABLE_RESOURCE_TO_PROCESS_SECURITY @ "TLS is not configured on this server",
+    unsupported_mechanism(mechanism: &str) => COMMAND_NOT_IMPLEMENTED_FOR_PARAMETER
+        @ format!("unsupported security mechanism '{}', use TLS", mechanism)
+});
+
+define_replies!(ccc {
+    // Once secured, we keep the control connection that way.
+    refused() => REQUEST_DENIED_FOR_POLICY_REASONS @ "the control connection must stay protected"
+});
+
 define_replies!(cdup {
     success() => REQUESTED_FILE_ACTION_COMPLETED @ "changed to parent directory",
     no_parent() => REQUESTED_ACTION_NOT_TAKEN @ "there is no parent directory"
 });
 
 define_replies!(cwd {
-    success() => REQUESTED_FILE_ACTION_COMPLETED @ "changed working directory"
+    success() => REQUESTED_FILE_ACTION_COMPLETED @ "changed working directory",
+    not_a_directory() => REQUESTED_ACTION_NOT_TAKEN @ "not a directory"
+});
+
+define_replies!(dele {
+    success() => REQUESTED_FILE_ACTION_COMPLETED @ "file deleted"
+});
+
+define_replies!(eprt {
+    success() => OK @ "EPRT command successful"
 });
 
 define_replies!(epsv {
     success(port: u16) => ENTERING_PASSIVE_MODE_EXTENDED
-        @ format!("passive mode enabled (|||{}|)", port)
+        @ format!("passive mode enabled (|||{}|)", port),
+    all() => OK @ "EPSV ALL accepted, only EPSV may be used from now on",
+    // RFC 2428 asks for the supported protocols in brackets.
+    unsupported_protocol(supported: u8) => NETWORK_PROTOCOL_NOT_SUPPORTED
+        @ format!("network protocol not supported, use ({})", supported)
+});
+
+define_replies!(mdtm {
+    success(modified: &str) => FILE_STATUS @ modified,
+    not_a_file() => REQUESTED_ACTION_NOT_TAKEN @ "not a plain file"
 });
 
 define_replies!(mkd {
     success() => PATHNAME_CREATED @ "created directory"
 });
 
+define_replies!(mlsd {
+    not_a_directory() => REQUESTED_ACTION_NOT_TAKEN @ "not a directory"
+});
+
+define_replies!(nlst {
+    no_matches() => REQUESTED_FILE_ACTION_NOT_TAKEN @ "no files found"
+});
+
+define_replies!(opts {
+    mlst(facts: &str) => OK @ format!("MLST OPTS {}", facts),
+    unsupported(command_name: &str) => SYNTAX_ERROR
+        @ format!("no options are supported for {}", command_name)
+});
+
 define_replies!(pass {
     logged_in() => USER_LOGGED_IN @ "user logged in",
     not_logged_in(reason: &str) => USER_NOT_LOGGED_IN @ reason
 });
 
 define_replies!(pasv {
-    success(port: u16) => ENTERING_PASSIVE_MODE
-        @ format!("passive mode enabled (127,0,0,1,{})", port)
+    success(addr: ::std::net::SocketAddrV4) => ENTERING_PASSIVE_MODE
+        @ {
+            let ip = addr.ip().octets();
+            format!("passive mode enabled ({},{},{},{},{},{})",
+                    ip[0], ip[1], ip[2], ip[3], addr.port() >> 8, addr.port() & 0xff)
+        },
+    ipv4_only() => CANT_OPEN_DATA_CONNECTION @ "PASV only supports IPv4, use EPSV instead"
+});
+
+define_replies!(pbsz {
+    // TLS does its own buffering, so RFC 4217 always uses zero.
+    success() => OK @ "PBSZ=0"
+});
+
+define_replies!(port {
+    success() => OK @ "PORT command successful"
+});
+
+define_replies!(prot {
+    success() => OK @ "data protection level set",
+    unsupported_level() => PROTECTION_LEVEL_NOT_SUPPORTED @ "only PROT C and PROT P are supported"
 });
 
 define_replies!(pwd {
@@ -60,12 +128,58 @@ define_replies!(pwd {
         @ format!("\"{}\"", working_dir.display())
 });
 
+define_replies!(rest {
+    success(marker: u64) => REQUESTED_FILE_ACTION_PENDING_FURTHER_INFORMATION
+        @ format!("restarting at {}, send RETR or STOR to resume", marker),
+    invalid_marker(file_size: u64) => INVALID_REST_PARAMETER
+        @ format!("restart marker is past the end of the file ({} bytes)", file_size)
+});
+
+define_replies!(rmd {
+    success() => REQUESTED_FILE_ACTION_COMPLETED @ "directory removed"
+});
+
+define_replies!(rnfr {
+    ready() => REQUESTED_FILE_ACTION_PENDING_FURTHER_INFORMATION @ "ready for destination name"
+});
+
+define_replies!(rnto {
+    success() => REQUESTED_FILE_ACTION_COMPLETED @ "file renamed",
+    no_source() => BAD_COMMAND_SEQUENCE @ "RNFR must immediately precede RNTO"
+});
+
+define_replies!(size {
+    success(size: u64) => FILE_STATUS @ size.to_string(),
+    not_a_file() => REQUESTED_ACTION_NOT_TAKEN @ "not a plain file"
+});
+
 define_replies!(syst {
     // * `os` is the operating system. It should be one of the
     // assigned constants from RFC 943.
     success(os: String) => SYSTEM_NAME_TYPE @ os
 });
 
+define_replies!(transfer {
+    complete() => CLOSING_DATA_CONNECTION @ "transfer complete",
+    complete_unique(file_name: &str) => CLOSING_DATA_CONNECTION
+        @ format!("transfer complete (FILE: {})", file_name),
+    no_data_connection() => CANT_OPEN_DATA_CONNECTION @ "no data connection, send PORT or PASV first",
+    cannot_listen(reason: &str) => CANT_OPEN_DATA_CONNECTION
+        @ format!("could not open data connection: {}", reason),
+    timed_out() => CANT_OPEN_DATA_CONNECTION @ "timed out waiting for the data connection",
+    cannot_connect(reason: &str) => CANT_OPEN_DATA_CONNECTION
+        @ format!("could not connect to the client: {}", reason),
+    // RFC 2577 asks for 504 when refusing an address.
+    address_not_allowed(reason: &str) => COMMAND_NOT_IMPLEMENTED_FOR_PARAMETER @ reason,
+    aborted() => CONNECTION_CLOSED_TRANSFER_ABORTED @ "transfer aborted",
+    connection_lost(reason: &str) => CONNECTION_CLOSED_TRANSFER_ABORTED
+        @ format!("data connection lost: {}", reason),
+    protection_required() => DATA_CONNECTION_CANNOT_BE_OPENED_WITH_PROT
+        @ "data connections must be protected, send PROT P first",
+    local_error(reason: &str) => REQUESTED_ACTION_ABORTED_LOCAL_ERROR_IN_PROCESSING @ reason,
+    exceeded_storage(reason: &str) => REQUESTED_FILE_ACTION_ABORTED_EXCEEDED_ALLOCATION @ reason
+});
+
 define_replies!(user {
     logged_in() => USER_LOGGED_IN @ "user logged in",
     need_password() => USER_NAME_OKAY_NEED_PASSWORD @ "need password",
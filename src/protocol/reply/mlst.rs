@@ -0,0 +1,17 @@
+//! The reply to an 'MLST' command.
+
+use {Reply, reply};
+use facts::{Entry, FactKind};
+
+/// Builds the reply describing a single file.
+///
+/// * `facts` are the kinds of facts the client asked for.
+pub fn success(entry: &Entry, facts: &[FactKind]) -> Reply {
+    Reply::multi_line(reply::code::REQUESTED_FILE_ACTION_COMPLETED, vec![
+        format!("Listing {}", entry.name),
+        // The entry line must start with a space, which the
+        // reply writer adds for us.
+        entry.to_line(facts),
+        "End".to_owned(),
+    ])
+}
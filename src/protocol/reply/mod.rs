@@ -4,9 +4,12 @@ pub use self::commands::*;
 pub mod code;
 // FIXME: make this private
 pub mod feat;
+pub mod mlst;
 
 mod commands;
 
+use {Error, ErrorKind};
+
 use std::io::prelude::*;
 use std::{io, fmt};
 
@@ -58,12 +61,64 @@ impl Reply
         }
     }
 
+    /// Reads a single or multi-line reply.
+    ///
+    /// Lines in the middle of a multi-line reply may or may not start
+    /// with the code followed by '-'. One leading space is removed
+    /// from lines that don't, which undoes the indentation `write`
+    /// adds.
+    pub fn read(read: &mut BufRead) -> Result<Self, Error> {
+        let first_line = read_line(read)?;
+        let (code, separator, text) = split_reply_line(&first_line)?;
+
+        if separator != Some('-') {
+            return Ok(Reply::single_line(code, text));
+        }
+
+        let mut lines = vec![text.to_owned()];
+        loop {
+            let line = read_line(read)?;
+
+            // The reply ends with a line starting with the same code.
+            if let Ok((line_code, separator, text)) = split_reply_line(&line) {
+                if line_code == code {
+                    lines.push(text.to_owned());
+
+                    if separator == Some('-') { continue } else { break }
+                }
+            }
+
+            let text = if line.starts_with(' ') { &line[1..] } else { &line[..] };
+            lines.push(text.to_owned());
+        }
+
+        Ok(Reply::multi_line(code, lines))
+    }
+
     pub fn write(&self, write: &mut Write) -> Result<(), io::Error> {
         match self.text {
             Text::SingleLine(ref line) => {
                 write!(write, "{} {}\r\n", self.code.0, line)
             },
-            Text::MultiLine(..) => unimplemented!(),
+            Text::MultiLine(ref lines) => {
+                let (last, rest) = match lines.split_last() {
+                    Some(split) => split,
+                    None => return write!(write, "{} \r\n", self.code.0),
+                };
+
+                for (i, line) in rest.iter().enumerate() {
+                    // Only the first line has the code. We indent the rest
+                    // so that a line starting with digits can never be
+                    // mistaken for the end of the reply.
+                    if i == 0 {
+                        write!(write, "{}-{}\r\n", self.code.0, line)?;
+                    } else {
+                        write!(write, " {}\r\n", line)?;
+                    }
+                }
+
+                write!(write, "{} {}\r\n", self.code.0, last)
+            },
         }
     }
 }
@@ -72,16 +127,52 @@ impl From<String> for Text
 {
     fn from(s: String) -> Text {
         let lines: Vec<_> = s.lines().collect();
-        assert_eq!(lines.is_empty(), false);
 
-        if lines.len() == 1 {
-            Text::SingleLine(lines[0].to_owned())
+        if lines.len() <= 1 {
+            Text::SingleLine(lines.first().cloned().unwrap_or("").to_owned())
         } else {
             Text::MultiLine(lines.into_iter().map(|l| l.to_owned()).collect())
         }
     }
 }
 
+/// Reads a line of a reply, without the line ending.
+fn read_line(read: &mut BufRead) -> Result<String, Error> {
+    let mut line = Vec::new();
+    read.read_until(b'\n', &mut line)?;
+
+    if line.last() != Some(&b'\n') {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                  "connection closed in the middle of a reply").into());
+    }
+
+    line.pop();
+    if line.last() == Some(&b'\r') { line.pop(); }
+
+    Ok(String::from_utf8(line)?)
+}
+
+/// Splits a reply line into its code, the character after the
+/// code and the text.
+fn split_reply_line(line: &str) -> Result<(Code, Option<char>, &str), Error> {
+    let invalid = || Error::from_kind(ErrorKind::InvalidReply(
+        format!("reply should start with a 3 digit code: '{}'", line)));
+
+    let digits = line.bytes().take_while(|b| b.is_ascii_digit()).count();
+    if digits != 3 {
+        return Err(invalid());
+    }
+
+    let code: u16 = line[..3].parse().map_err(|_| invalid())?;
+    let separator = line[3..].chars().next();
+
+    match separator {
+        Some(' ') | Some('-') => Ok((Code(code), separator, &line[4..])),
+        None => Ok((Code(code), None, "")),
+        Some(..) => Err(invalid()),
+    }
+}
+
 impl fmt::Display for Text
 {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -95,3 +186,129 @@ impl fmt::Display for Text
     }
 }
 
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use std::io;
+
+    fn write(reply: Reply) -> String {
+        let mut buffer = io::Cursor::new(Vec::new());
+        reply.write(&mut buffer).unwrap();
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn correctly_writes_single_line_replies() {
+        assert_eq!(write(Reply::new(200, "ok")), "200 ok\r\n");
+    }
+
+    #[test]
+    fn correctly_writes_multi_line_replies() {
+        let reply = Reply::multi_line(211, vec![
+            "Extensions supported:".to_owned(),
+            "SIZE".to_owned(),
+            "END".to_owned(),
+        ]);
+
+        assert_eq!(write(reply), "211-Extensions supported:\r\n SIZE\r\n211 END\r\n");
+    }
+
+    #[test]
+    fn correctly_writes_one_line_multi_line_replies() {
+        assert_eq!(write(Reply::multi_line(211, vec!["END".to_owned()])), "211 END\r\n");
+    }
+
+    #[test]
+    fn escapes_lines_starting_with_digits() {
+        let reply = Reply::multi_line(214, vec![
+            "The following commands are recognized:".to_owned(),
+            "214 is not the end".to_owned(),
+            "Help OK".to_owned(),
+        ]);
+
+        assert_eq!(write(reply),
+                   "214-The following commands are recognized:\r\n 214 is not the end\r\n214 Help OK\r\n");
+    }
+
+    #[test]
+    fn correctly_converts_empty_text() {
+        assert_eq!(Reply::new(200, ""), Reply::single_line(200, ""));
+    }
+
+    mod read
+    {
+        use super::*;
+        use std::io;
+
+        fn read(text: &str) -> Result<Reply, Error> {
+            Reply::read(&mut io::Cursor::new(text))
+        }
+
+        #[test]
+        fn correctly_reads_single_line_replies() {
+            assert_eq!(read("220 Service ready\r\n").unwrap(), Reply::single_line(220, "Service ready"));
+            assert_eq!(read("200\n").unwrap(), Reply::single_line(200, ""));
+        }
+
+        #[test]
+        fn correctly_reads_multi_line_replies() {
+            let text = "211-Extensions supported:\r\n SIZE\r\n 211 MDTM\r\n211 END\r\n";
+            assert_eq!(read(text).unwrap(), Reply::multi_line(211, vec![
+                "Extensions supported:".to_owned(),
+                "SIZE".to_owned(),
+                "211 MDTM".to_owned(),
+                "END".to_owned(),
+            ]));
+        }
+
+        #[test]
+        fn correctly_reads_lines_that_repeat_the_code() {
+            let text = "230-Welcome\r\n230-to the server\r\n230 Logged in\r\n";
+            assert_eq!(read(text).unwrap(), Reply::multi_line(230, vec![
+                "Welcome".to_owned(),
+                "to the server".to_owned(),
+                "Logged in".to_owned(),
+            ]));
+        }
+
+        #[test]
+        fn reads_what_it_writes() {
+            let reply = Reply::multi_line(250, vec![
+                "Listing /".to_owned(),
+                "type=dir; /".to_owned(),
+                "End".to_owned(),
+            ]);
+
+            let mut buffer = io::Cursor::new(Vec::new());
+            reply.write(&mut buffer).unwrap();
+            buffer.set_position(0);
+
+            assert_eq!(Reply::read(&mut buffer).unwrap(), reply);
+        }
+
+        #[test]
+        fn reads_replies_one_at_a_time() {
+            let mut buffer = io::Cursor::new("331 Password required\r\n230 Logged in\r\n");
+            assert_eq!(Reply::read(&mut buffer).unwrap().code, Code(331));
+            assert_eq!(Reply::read(&mut buffer).unwrap().code, Code(230));
+        }
+
+        #[test]
+        fn rejects_malformed_replies() {
+            for text in &["hello\r\n", "20 ok\r\n", "2000 ok\r\n", "200x\r\n"] {
+                match read(text) {
+                    Err(Error(ErrorKind::InvalidReply(..), _)) => (),
+                    result => panic!("unexpected result for {:?}: {:?}", text, result),
+                }
+            }
+        }
+
+        #[test]
+        fn rejects_unfinished_replies() {
+            assert!(read("").is_err());
+            assert!(read("211-Extensions supported:\r\n SIZE\r\n").is_err());
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Calendar dates for timestamps.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+const MONTH_ABBREVIATIONS: [&'static str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun",
+    "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A date and time in UTC.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime
+{
+    pub year: u64,
+    /// The month, from 1 to 12.
+    pub month: u32,
+    /// The day of the month, starting from 1.
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime
+{
+    /// Gets the UTC date of a timestamp.
+    ///
+    /// Timestamps from before 1970 are treated as if they were at 1970.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let (year, month, day) = civil_from_days(seconds / SECONDS_PER_DAY);
+        let seconds_of_day = (seconds % SECONDS_PER_DAY) as u32;
+
+        DateTime {
+            year: year,
+            month: month,
+            day: day,
+            hour: seconds_of_day / 3600,
+            minute: (seconds_of_day / 60) % 60,
+            second: seconds_of_day % 60,
+        }
+    }
+
+    /// Parses a time in the `YYYYMMDDHHMMSS[.sss]` format from RFC 3659.
+    ///
+    /// Fractions of a second are ignored.
+    pub fn parse_timeval(text: &str) -> Option<Self> {
+        let text = text.split('.').next().unwrap();
+        if text.len() != 14 || !text.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let number = |range: ::std::ops::Range<usize>| text[range].parse::<u32>().unwrap();
+        let date_time = DateTime {
+            year: text[0..4].parse().unwrap(),
+            month: number(4..6),
+            day: number(6..8),
+            hour: number(8..10),
+            minute: number(10..12),
+            second: number(12..14),
+        };
+
+        let is_valid = date_time.year >= 1970 &&
+            date_time.month >= 1 && date_time.month <= 12 &&
+            date_time.day >= 1 && date_time.day <= 31 &&
+            date_time.hour < 24 && date_time.minute < 60 && date_time.second < 60;
+
+        if is_valid { Some(date_time) } else { None }
+    }
+
+    /// Formats the time as `YYYYMMDDHHMMSS`, as used by RFC 3659.
+    pub fn timeval(&self) -> String {
+        format!("{:04}{:02}{:02}{:02}{:02}{:02}",
+                self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+
+    /// Converts the date back into a timestamp.
+    pub fn to_system_time(&self) -> SystemTime {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = days * SECONDS_PER_DAY +
+            (self.hour as u64 * 3600) + (self.minute as u64 * 60) + self.second as u64;
+
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    /// Gets the three letter English abbreviation of the month.
+    pub fn month_abbreviation(&self) -> &'static str {
+        MONTH_ABBREVIATIONS[self.month as usize - 1]
+    }
+}
+
+/// Converts a number of days since 1970-01-01 into a (year, month, day).
+///
+/// This is Howard Hinnant's `civil_from_days` algorithm, restricted
+/// to dates after the epoch.
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    // Shift the epoch to 0000-03-01 so that leap days fall at the end of the year.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// Converts a (year, month, day) into the number of days since 1970-01-01.
+///
+/// This is the inverse of `civil_from_days`.
+fn days_from_civil(year: u64, month: u32, day: u32) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 } as u64;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day as u64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod test
+{
+    pub use super::*;
+
+    fn date_time(seconds: u64) -> DateTime {
+        DateTime::from_system_time(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn correctly_converts_the_epoch() {
+        assert_eq!(date_time(0), DateTime {
+            year: 1970, month: 1, day: 1, hour: 0, minute: 0, second: 0,
+        });
+    }
+
+    #[test]
+    fn correctly_converts_a_timestamp() {
+        assert_eq!(date_time(1_500_000_000), DateTime {
+            year: 2017, month: 7, day: 14, hour: 2, minute: 40, second: 0,
+        });
+    }
+
+    #[test]
+    fn correctly_converts_leap_days() {
+        assert_eq!(date_time(951_782_400), DateTime {
+            year: 2000, month: 2, day: 29, hour: 0, minute: 0, second: 0,
+        });
+        assert_eq!(date_time(951_868_800).month_abbreviation(), "Mar");
+    }
+
+    #[test]
+    fn correctly_formats_timevals() {
+        assert_eq!(date_time(1_500_000_000).timeval(), "20170714024000");
+    }
+
+    #[test]
+    fn correctly_parses_timevals() {
+        assert_eq!(DateTime::parse_timeval("20170714024000"), Some(date_time(1_500_000_000)));
+        assert_eq!(DateTime::parse_timeval("20170714024000.123"), Some(date_time(1_500_000_000)));
+        assert_eq!(DateTime::parse_timeval("2017071402400"), None);
+        assert_eq!(DateTime::parse_timeval("20171314024000"), None);
+    }
+
+    #[test]
+    fn correctly_converts_back_to_timestamps() {
+        for &seconds in [0, 951_782_400, 1_500_000_000, 4_102_444_799].iter() {
+            assert_eq!(date_time(seconds).to_system_time(), UNIX_EPOCH + Duration::from_secs(seconds));
+        }
+    }
+
+    #[test]
+    fn clamps_times_before_the_epoch() {
+        assert_eq!(DateTime::from_system_time(UNIX_EPOCH - Duration::from_secs(10)), date_time(0));
+    }
+}
@@ -1,20 +1,28 @@
 use server::Transfer;
-use io::DataTransferMode;
-use protocol;
+use protocol::{self, NetworkProtocol};
 
 /// An action to take after receiving a command.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum Action
 {
     /// Reply to the command normally.
     Reply(protocol::Reply),
-    /// Establish a data connection.
-    EstablishDataConnection {
-        /// The reply to the original command.
-        reply: protocol::Reply,
-        /// The mode for the data connection.
-        mode: DataTransferMode,
+    /// Listen for the client to open a data connection.
+    ListenPassive {
+        /// Whether the client sent 'EPSV', which only needs to be
+        /// told the port.
+        extended: bool,
+        /// The network protocol the client asked us to listen with.
+        network_protocol: Option<NetworkProtocol>,
     },
+    /// Send the reply, then protect the control connection with TLS.
+    StartTls(protocol::Reply),
     /// Transfer data.
     Transfer(Transfer),
+    /// Cancel the data transfer, if there is one, and close the
+    /// data connection.
+    Abort {
+        /// The transfer that was in progress.
+        transfer: Option<Transfer>,
+    },
 }
@@ -1,14 +1,36 @@
-use {Error, protocol};
-use io::{Connection, DataTransfer, DataTransferMode, Io};
-use server::Server;
+use {Error, ErrorKind, server, protocol};
+use io::{Connection, DataTransfer, DataTransferMode, Io, Stream};
+use server::{Server, Direction};
 use server::client::{ClientState, Session};
 
 use std::io::prelude::*;
+use std::net::SocketAddr;
+use std::{fmt, io};
 use std;
 
-use mio::unix::UnixReady;
 use mio;
 
+/// The number of bytes we read from a DTP stream at a time.
+const RECEIVE_BUFFER_SIZE: usize = 8192;
+
+/// The number of bytes we read from a file at a time when sending it.
+const SEND_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The most data we will send or receive for a client in one tick.
+///
+/// Transfers are split up so that we keep reading the control
+/// connection, which is how the client can abort them.
+const MAX_BYTES_PER_TICK: usize = 1024 * 1024;
+
+/// Why a transfer could not be finished.
+enum TransferError
+{
+    /// The data connection failed.
+    Connection(io::Error),
+    /// Reading or writing the file failed.
+    File(io::Error),
+}
+
 /// A client from the perspective of a server.
 pub struct Client
 {
@@ -22,8 +44,8 @@ impl Client
 {
     /// Attempts to update the state of the client with any
     /// information received from the network.
-    pub fn tick(&mut self, io: &mut Io) -> Result<(), Error> {
-        self::tick(&mut self.state, &mut self.connection, io)
+    pub fn tick(&mut self, server: &mut Server, io: &mut Io) -> Result<(), Error> {
+        self::tick(&mut self.state, &mut self.connection, server, io)
     }
 
     pub fn handle_io_event(&mut self,
@@ -34,60 +56,226 @@ impl Client
         -> Result<(), Error> {
         super::client_io::handle_event(&mut self.state, event,
                                        &mut self.connection, the_token,
-                                       server, io)
+                                       server, io)?;
+
+        // The data connection is ready for more, so carry on with
+        // the transfer now rather than waiting for the next tick.
+        if the_token != self.connection.pi.token {
+            self::tick(&mut self.state, &mut self.connection, server, io)?;
+        }
+
+        Ok(())
+    }
+
+    /// Cleans up after a client that has gone away.
+    ///
+    /// Anything we received of an unfinished upload is thrown away.
+    pub fn close(self, server: &mut Server) {
+        if let Session::Ready(session) = self.state.session {
+            if let Some(transfer) = session.active_transfer {
+                transfer.discard(server.file_system_mut());
+            }
+        }
     }
 }
 
 /// Does the state tick.
 fn tick(state: &mut ClientState,
         connection: &mut Connection,
+        server: &mut Server,
         io: &mut Io) -> Result<(), Error> {
     match state.session {
         Session::Ready(ref mut session) => {
             let active_transfer = std::mem::replace(&mut session.active_transfer, None);
 
             if let Some(active_transfer) = active_transfer {
-                let dtp = std::mem::replace(&mut connection.dtp, DataTransfer::None);
+                let dtp = match std::mem::replace(&mut connection.dtp, DataTransfer::None) {
+                    // The client sent 'PORT' after asking for a passive connection.
+                    DataTransfer::Listening { .. } if session.data_transfer_mode == DataTransferMode::Active => {
+                        DataTransfer::None
+                    },
+                    dtp => dtp,
+                };
 
                 debug!("server is ready and we have an active transfer");
                 connection.dtp = match dtp {
                     DataTransfer::None => {
-                        assert_eq!(session.data_transfer_mode, DataTransferMode::Active);
-
-                        let client_addr = session.client_addr.expect("attempted a transfer but client address is not set");
-                        let stream = mio::tcp::TcpStream::connect(&client_addr)?;
+                        let client_addr = match (session.data_transfer_mode, session.client_addr) {
+                            (DataTransferMode::Active, Some(client_addr)) => client_addr,
+                            _ => {
+                                // The client hasn't told us how to open the data connection.
+                                active_transfer.discard(server.file_system_mut());
+                                connection.send_reply(protocol::reply::transfer::no_data_connection())?;
+                                return Ok(());
+                            },
+                        };
 
-                        let token = io.allocate_token();
-                        io.poll.register(&stream, token,
-                                         mio::Ready::readable() | UnixReady::hup() |
-                                         mio::Ready::writable(),
-                                         mio::PollOpt::edge())?;
+                        // RFC 959 says active connections come from port L-1,
+                        // but we can only use it if we are allowed to.
+                        let local_addr = if server.active_from_data_port() {
+                            let control_port = connection.pi.stream.socket().local_addr()?.port();
+                            Some(SocketAddr::new(connection.pi.local_ip()?, control_port.saturating_sub(1)))
+                        } else {
+                            None
+                        };
 
-                        debug!("establishing a DTP connection for ACTIVE mode");
+                        debug!("establishing a DTP connection to {} for ACTIVE mode", client_addr);
 
-                        // We aren't ready to send data just yet.
-                        session.active_transfer = Some(active_transfer);
-
-                        DataTransfer::Connecting {
-                            stream: stream,
-                            token: token,
+                        match DataTransfer::connect(&client_addr, local_addr, io) {
+                            Ok(dtp) => {
+                                // We aren't ready to send data just yet.
+                                session.active_transfer = Some(active_transfer);
+                                dtp
+                            },
+                            Err(e) => {
+                                info!("could not connect to {}: {}", client_addr, e);
+                                active_transfer.discard(server.file_system_mut());
+                                connection.send_reply(protocol::reply::transfer::cannot_connect(&e.to_string()))?;
+                                DataTransfer::None
+                            },
                         }
                     },
-                    DataTransfer::Connected { mut stream, .. } => {
-                        debug!("DTP stream is connected, sending data");
+                    DataTransfer::Connected { mut stream, token } => {
+                        let unique_file_name = active_transfer.unique_file_name();
+                        let server::Transfer { file_type, direction } = active_transfer;
 
-                        connection.send_command(&protocol::command::TYPE {
-                            file_type: active_transfer.file_type,
-                        })?;
+                        match direction {
+                            Direction::Outgoing { mut reader, mut chunk, mut position } => {
+                                debug!("DTP stream is connected, sending data");
 
-                        stream.write(&active_transfer.data)?;
-                        stream.flush()?;
-                        drop(stream);
+                                let finished = match send(&mut stream, &mut reader, &mut chunk, &mut position) {
+                                    Ok(finished) => finished,
+                                    Err(e) => {
+                                        info!("could not send data: {}", e);
+                                        connection.send_reply(e.reply())?;
+                                        return Ok(());
+                                    },
+                                };
 
-                        connection.send_reply(protocol::Reply::new(226, "Transfer complete"))?;
+                                // Shutting down the stream makes sure the client has been
+                                // sent everything before we tell them that we're done.
+                                let closed = if finished {
+                                    match stream.shutdown() {
+                                        Ok(()) => true,
+                                        // A TLS stream still has data to send.
+                                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => false,
+                                        Err(e) => {
+                                            info!("could not close data connection: {}", e);
+                                            connection.send_reply(protocol::reply::transfer::connection_lost(&e.to_string()))?;
+                                            return Ok(());
+                                        },
+                                    }
+                                } else {
+                                    false
+                                };
 
-                        debug!("completed active transfer");
-                        DataTransfer::None
+                                if closed {
+                                    drop(stream);
+
+                                    connection.send_reply(protocol::reply::transfer::complete())?;
+
+                                    debug!("completed active transfer");
+                                    DataTransfer::None
+                                } else {
+                                    // The client isn't ready for the rest of the data yet.
+                                    session.active_transfer = Some(server::Transfer {
+                                        file_type: file_type,
+                                        direction: Direction::Outgoing {
+                                            reader: reader,
+                                            chunk: chunk,
+                                            position: position,
+                                        },
+                                    });
+
+                                    DataTransfer::Connected { stream: stream, token: token }
+                                }
+                            },
+                            Direction::Incoming { path, mode, file } => {
+                                let mut file = match file {
+                                    Some(file) => file,
+                                    None => match mode.open(&path, server.file_system_mut()) {
+                                        Ok(file) => file,
+                                        Err(Error(ErrorKind::Io(e), _)) => {
+                                            info!("could not open {}: {}", path.display(), e);
+                                            connection.send_reply(super::client_io::file_system_error(&e))?;
+                                            return Ok(());
+                                        },
+                                        Err(e) => return Err(e),
+                                    },
+                                };
+
+                                let finished = match receive(&mut stream, &mut file.writer) {
+                                    Ok(finished) => finished,
+                                    Err(e) => {
+                                        info!("could not receive {}: {}", path.display(), e);
+                                        mode.discard(file, server.file_system_mut());
+                                        connection.send_reply(e.reply())?;
+                                        return Ok(());
+                                    },
+                                };
+
+                                // TLS clients wait for us to close the session too.
+                                let closed = if finished {
+                                    match stream.shutdown() {
+                                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => false,
+                                        // We have the whole file, so it doesn't matter
+                                        // if the client is already gone.
+                                        _ => true,
+                                    }
+                                } else {
+                                    false
+                                };
+
+                                if closed {
+                                    drop(stream);
+
+                                    let reply = match mode.finish(&path, file, server.file_system_mut()) {
+                                        Ok(()) => {
+                                            debug!("completed upload of {}", path.display());
+
+                                            match unique_file_name {
+                                                Some(file_name) => protocol::reply::transfer::complete_unique(&file_name),
+                                                None => protocol::reply::transfer::complete(),
+                                            }
+                                        },
+                                        Err(Error(ErrorKind::Io(e), _)) => {
+                                            info!("could not store {}: {}", path.display(), e);
+                                            super::client_io::file_system_error(&e)
+                                        },
+                                        Err(e) => return Err(e),
+                                    };
+                                    connection.send_reply(reply)?;
+
+                                    DataTransfer::None
+                                } else {
+                                    // The client hasn't finished sending the file yet.
+                                    session.active_transfer = Some(server::Transfer {
+                                        file_type: file_type,
+                                        direction: Direction::Incoming { path: path, mode: mode, file: Some(file) },
+                                    });
+
+                                    DataTransfer::Connected { stream: stream, token: token }
+                                }
+                            },
+                        }
+                    },
+                    DataTransfer::Listening { listener, token, waiting_since } => {
+                        if waiting_since.elapsed() >= server.passive_timeout() {
+                            info!("timed out waiting for a passive data connection");
+                            active_transfer.discard(server.file_system_mut());
+
+                            // Dropping the listener frees up the port.
+                            connection.send_reply(protocol::reply::transfer::timed_out())?;
+                            DataTransfer::None
+                        } else {
+                            // The client hasn't connected yet.
+                            session.active_transfer = Some(active_transfer);
+                            DataTransfer::Listening {
+                                listener: listener,
+                                token: token,
+                                waiting_since: waiting_since,
+                            }
+                        }
                     },
                     state => {
                         // We aren't ready to send data just yet.
@@ -102,3 +290,168 @@ fn tick(state: &mut ClientState,
         _ => Ok(())
     }
 }
+
+/// Sends as much of a file as the DTP stream will currently take.
+///
+/// The file is read a chunk at a time, so only one chunk is
+/// ever held in memory. Whatever the socket doesn't accept is kept
+/// in the chunk until it next becomes writable.
+///
+/// Returns `true` once all of it has been sent.
+fn send(stream: &mut Stream,
+        reader: &mut Read,
+        chunk: &mut Vec<u8>,
+        position: &mut usize) -> Result<bool, TransferError> {
+    let mut bytes_sent = 0;
+
+    while bytes_sent < MAX_BYTES_PER_TICK {
+        if *position == chunk.len() {
+            chunk.resize(SEND_CHUNK_SIZE, 0);
+
+            let bytes_read = loop {
+                match reader.read(chunk) {
+                    Ok(bytes_read) => break bytes_read,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(TransferError::File(e)),
+                }
+            };
+
+            chunk.truncate(bytes_read);
+            *position = 0;
+
+            if bytes_read == 0 { return Ok(true) };
+        }
+
+        match stream.write(&chunk[*position..]) {
+            Ok(0) => return Err(TransferError::Connection(
+                io::Error::new(io::ErrorKind::WriteZero, "connection closed"))),
+            Ok(bytes_written) => {
+                *position += bytes_written;
+                bytes_sent += bytes_written;
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(TransferError::Connection(e)),
+        }
+    }
+
+    Ok(false)
+}
+
+/// Writes what is currently available on the DTP stream into a file.
+///
+/// Returns `true` once the client has closed the connection, which
+/// marks the end of the file.
+fn receive(stream: &mut Stream, writer: &mut Write)
+    -> Result<bool, TransferError> {
+    let mut buffer: [u8; RECEIVE_BUFFER_SIZE] = [0; RECEIVE_BUFFER_SIZE];
+    let mut bytes_received = 0;
+
+    while bytes_received < MAX_BYTES_PER_TICK {
+        match stream.read(&mut buffer) {
+            Ok(0) => {
+                writer.flush().map_err(TransferError::File)?;
+                return Ok(true);
+            },
+            Ok(bytes_read) => {
+                writer.write_all(&buffer[0..bytes_read]).map_err(TransferError::File)?;
+                bytes_received += bytes_read;
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(TransferError::Connection(e)),
+        }
+    }
+
+    Ok(false)
+}
+
+impl TransferError
+{
+    /// Gets the reply that should be sent to the client.
+    fn reply(&self) -> protocol::Reply {
+        match *self {
+            TransferError::Connection(ref e) => protocol::reply::transfer::connection_lost(&e.to_string()),
+            TransferError::File(ref e) if is_out_of_space(e) => {
+                protocol::reply::transfer::exceeded_storage(&e.to_string())
+            },
+            TransferError::File(ref e) => protocol::reply::transfer::local_error(&e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TransferError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransferError::Connection(ref e) => write!(fmt, "data connection lost: {}", e),
+            TransferError::File(ref e) => write!(fmt, "{}", e),
+        }
+    }
+}
+
+/// Checks if an IO error was caused by running out of storage.
+fn is_out_of_space(error: &io::Error) -> bool {
+    match error.kind() {
+        io::ErrorKind::StorageFull |
+            io::ErrorKind::QuotaExceeded |
+            io::ErrorKind::FileTooLarge => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::MAX_BYTES_PER_TICK;
+    use fs::FileSystem;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn finishes_downloads_once_everything_is_sent() {
+        // Far more than one tick's worth, and more than fits in the
+        // socket buffers while the client is not reading.
+        let size = 32 * MAX_BYTES_PER_TICK;
+        let contents: Vec<u8> = (0..size).map(|i| i as u8).collect();
+
+        let mut server = TestServer::new();
+        server.file_system.write_file(Path::new("/big.bin"), contents.clone()).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        let mut data = control.passive();
+        control.start_transfer("RETR big.bin");
+        assert_eq!(control.reply_within(Duration::from_millis(500)), None);
+
+        assert!(data.read_all() == contents);
+        assert_eq!(control.reply().code.0, 226);
+    }
+
+    #[test]
+    fn gives_up_waiting_for_passive_connections() {
+        let mut server = TestServer::new();
+        server.file_system.write_file(Path::new("/hello.txt"), b"hello".to_vec()).unwrap();
+        server.passive_timeout = Duration::from_millis(200);
+
+        // Only allow one port, so that it can only be used
+        // again once the listener has been closed.
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        server.passive_ports = port..=port;
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        assert_eq!(control.epsv().port(), port);
+        control.start_transfer("RETR hello.txt");
+        assert_eq!(control.reply().code.0, 425);
+
+        assert_eq!(control.epsv().port(), port);
+    }
+}
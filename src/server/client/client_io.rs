@@ -1,14 +1,18 @@
 use {Error, ErrorKind};
-use server::client::{ClientState, Action};
+use server::client::{ClientState, Action, Session};
 use server::Server;
-use io::{Connection, DataTransfer, DataTransferMode, Io};
+use io::{Connection, DataTransfer, DataTransferMode, Io, Line, Stream, unmap_ip};
 use protocol::reply::AsReplyCode;
+use protocol::{NetworkProtocol, ProtectionLevel};
 use protocol;
 
 use std::io::prelude::*;
+use std::net::{IpAddr, SocketAddrV4};
+use std::time::Instant;
 use std::io;
 use std;
 
+use mio::tcp::{TcpListener, TcpStream};
 use mio::unix::UnixReady;
 use mio;
 
@@ -20,134 +24,494 @@ pub fn handle_event(state: &mut ClientState,
                     server: &mut Server,
                     io: &mut Io)
     -> Result<(), Error> {
-    if the_token == connection.pi.token && event.readiness().is_readable() {
-        handle_protocol_event(state, event, connection, io, server)
+    if the_token == connection.pi.token {
+        // Hangups are dealt with by the server loop, and any other
+        // error shows up when we next read from the stream.
+        if event.readiness().is_readable() {
+            handle_protocol_event(state, connection, io, server)
+        } else {
+            Ok(())
+        }
     } else {
-        handle_data_event(event, connection, io)
+        handle_data_event(state, event, connection, server, io)
     }
 }
 
 /// Handles an IO event on the protocol stream.
 fn handle_protocol_event(state: &mut ClientState,
-                         event: &mio::Event,
                          connection: &mut Connection,
                          io: &mut Io,
                          server: &mut Server)
     -> Result<(), Error> {
-    let mut buffer: [u8; 10000] = [0; 10000];
-    let bytes_written = connection.pi.stream.read(&mut buffer)?;
-    let mut data = io::Cursor::new(&buffer[0..bytes_written]);
-
-    assert_eq!(event.readiness().is_readable(), true);
-
-    if !data.get_ref().is_empty() {
-        let command = protocol::CommandKind::read(&mut data)?;
-        let action = match state.handle_command(&command, server) {
-            Ok(action) => action,
-            Err(Error(ErrorKind::Protocol(e), _))  => {
-                // If it was state error, tell them.
-                Action::Reply(protocol::Reply::new(e.as_reply_code(),
-                    format!("error: {}", e)))
-            },
-            Err(e) => return Err(e),
-        };
+    let mut buffer: [u8; 4096] = [0; 4096];
+    loop {
+        match connection.pi.stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes_read) => connection.pi.input.push(&buffer[0..bytes_read]),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
 
-        match action {
-            Action::Reply(reply) => {
-                reply.write(&mut connection.pi.stream)?;
+    // Clients can send several commands at once, or split one over many reads.
+    while let Some(line) = connection.pi.input.next_line() {
+        match line {
+            Line::Complete(line) => {
+                handle_command_line(state, &line, connection, io, server)?;
+            },
+            Line::TooLong => {
+                connection.send_reply(protocol::Reply::new(protocol::reply::code::INVALID_COMMAND,
+                    "command line too long"))?;
             },
-            Action::EstablishDataConnection { reply, mode } => {
-                reply.write(&mut connection.pi.stream)?;
+        }
+    }
 
-                let mut session = state.session.expect_ready_mut()?;
-                session.data_transfer_mode = mode;
+    Ok(())
+}
 
-                match mode {
-                    DataTransferMode::Active => unimplemented!(),
-                    DataTransferMode::Passive { port } => {
-                        connection.dtp = DataTransfer::bind(port, io)?;
-                    }
-                }
-            },
-            Action::Transfer(transfer) => {
-                let mut session = state.session.expect_ready_mut()?;
-                session.active_transfer = Some(transfer);
+/// Parses and handles a single command line from the client.
+fn handle_command_line(state: &mut ClientState,
+                       line: &[u8],
+                       connection: &mut Connection,
+                       io: &mut Io,
+                       server: &mut Server)
+    -> Result<(), Error> {
+    let mut data = io::Cursor::new(line);
 
-                let reply = if let DataTransfer::Connected { .. } = connection.dtp {
-                    protocol::Reply::new(125, "transfer starting")
-                } else {
-                    protocol::Reply::new(150, "about to open data connection")
-                };
+    // Commands we can't parse are answered like any other
+    // protocol error, such as 'EPRT' with an unknown protocol.
+    let result = protocol::CommandKind::read(&mut data).map_err(Error::from)
+        .and_then(|command| state.handle_command(&command, server));
 
-                reply.write(&mut connection.pi.stream)?;
-            },
-        }
+    let action = match result {
+        Ok(action) => action,
+        Err(Error(ErrorKind::Protocol(e), _))  => {
+            // If it was state error, tell them.
+            Action::Reply(protocol::Reply::new(e.as_reply_code(),
+                format!("error: {}", e)))
+        },
+        // Command handlers do no network IO, so IO errors come from
+        // the file system.
+        Err(Error(ErrorKind::Io(e), _)) => Action::Reply(file_system_error(&e)),
+        Err(e) => return Err(e),
+    };
+
+    match action {
+        Action::Reply(reply) => {
+            connection.send_reply(reply)?;
+        },
+        Action::ListenPassive { extended, network_protocol } => {
+            let reply = listen_passive(state, connection, extended, network_protocol, server, io)?;
+            connection.send_reply(reply)?;
+        },
+        Action::StartTls(reply) => {
+            match server.tls_config() {
+                Some(config) => {
+                    // The reply is the last thing sent in plain text.
+                    connection.send_reply(reply)?;
+                    connection.pi.stream.start_tls(&config);
+
+                    // Anything pipelined after 'AUTH' was sent in plain text
+                    // and must not be treated as if it came over TLS.
+                    connection.pi.input.clear();
+                },
+                None => connection.send_reply(protocol::reply::auth::unavailable())?,
+            }
+        },
+        Action::Transfer(transfer) => {
+            let session = state.session.expect_ready_mut()?;
+
+            if server.requires_tls(&session.credentials.username) &&
+                state.data_protection != ProtectionLevel::Private {
+                // 'STOU' has already created its file.
+                transfer.discard(server.file_system_mut());
+                connection.send_reply(protocol::reply::transfer::protection_required())?;
+                return Ok(());
+            }
+
+            let (code, message) = if let DataTransfer::Connected { .. } = connection.dtp {
+                (protocol::reply::code::DATA_CONNECTION_ALREADY_OPEN_TRANSFER_STARTING, "transfer starting")
+            } else {
+                (protocol::reply::code::FILE_STATUS_OKAY, "about to open data connection")
+            };
+
+            // RFC 1123 requires us to tell the client the name we picked.
+            let reply = match transfer.unique_file_name() {
+                Some(file_name) => protocol::Reply::new(code, format!("FILE: {}", file_name)),
+                None => protocol::Reply::new(code, message),
+            };
+
+            // Any unfinished transfer is replaced.
+            if let Some(previous) = session.active_transfer.take() {
+                previous.discard(server.file_system_mut());
+            }
+            session.active_transfer = Some(transfer);
+
+            // The client has until the timeout to connect from now.
+            if let DataTransfer::Listening { ref mut waiting_since, .. } = connection.dtp {
+                *waiting_since = Instant::now();
+            }
+
+            connection.send_reply(reply)?;
+        },
+        Action::Abort { transfer } => {
+            // Dropping the stream or listener closes the data connection.
+            connection.dtp = DataTransfer::None;
+
+            // The aborted command gets its own reply before the one for 'ABOR'.
+            if let Some(transfer) = transfer {
+                transfer.discard(server.file_system_mut());
+
+                connection.send_reply(protocol::reply::transfer::aborted())?;
+                connection.send_reply(protocol::reply::abor::success())?;
+            } else {
+                connection.send_reply(protocol::reply::abor::no_transfer())?;
+            }
+        },
     }
 
     Ok(())
 }
 
+/// Opens a listener for a passive data connection.
+///
+/// Returns the reply to 'PASV' or 'EPSV'.
+fn listen_passive(state: &mut ClientState,
+                  connection: &mut Connection,
+                  extended: bool,
+                  network_protocol: Option<NetworkProtocol>,
+                  server: &mut Server,
+                  io: &mut Io)
+    -> Result<protocol::Reply, Error> {
+    // We listen on the interface the client connected to.
+    let local_ip = connection.pi.local_ip()?;
+
+    // We can only listen using the protocol the client connected with.
+    let local_protocol = NetworkProtocol::of(&local_ip);
+    if network_protocol.map_or(false, |protocol| protocol != local_protocol) {
+        return Ok(protocol::reply::epsv::unsupported_protocol(local_protocol.number()));
+    }
+
+    // 'PASV' can only give the client an IPv4 address.
+    let advertised_ip = match (server.passive_address(), local_ip) {
+        (Some(ip), _) | (None, IpAddr::V4(ip)) => Some(ip),
+        (None, IpAddr::V6(..)) => None,
+    };
+    if !extended && advertised_ip.is_none() {
+        return Ok(protocol::reply::pasv::ipv4_only());
+    }
+
+    // Any data connection we already had is replaced.
+    connection.dtp = DataTransfer::None;
+
+    let (dtp, port) = match DataTransfer::listen(local_ip, server.passive_ports(), io) {
+        Ok(listening) => listening,
+        Err(Error(ErrorKind::Io(e), _)) => {
+            info!("could not listen for a passive data connection: {}", e);
+            return Ok(protocol::reply::transfer::cannot_listen(&e.to_string()));
+        },
+        Err(e) => return Err(e),
+    };
+    connection.dtp = dtp;
+
+    let session = state.session.expect_ready_mut()?;
+    session.data_transfer_mode = DataTransferMode::Passive { port: port };
+    debug!("listening for a passive data connection on port {}", port);
+
+    Ok(match advertised_ip {
+        Some(ip) if !extended => protocol::reply::pasv::success(SocketAddrV4::new(ip, port)),
+        _ => protocol::reply::epsv::success(port),
+    })
+}
+
 /// Handles an IO event on the data stream.
-fn handle_data_event(event: &mio::Event,
+fn handle_data_event(state: &mut ClientState,
+                     event: &mio::Event,
                      connection: &mut Connection,
+                     server: &mut Server,
                      io: &mut Io)
     -> Result<(), Error> {
-    if event.readiness().is_writable() {
+    let readiness = UnixReady::from(event.readiness());
+
+    // A failed connection attempt shows up as an error or hangup.
+    if readiness.is_writable() || readiness.is_error() || readiness.is_hup() {
         let dtp = std::mem::replace(&mut connection.dtp,
                                     DataTransfer::None);
 
         connection.dtp = match dtp {
-            DataTransfer::None => unreachable!(),
-            DataTransfer::Listening { listener, .. } => {
-                let (sock, _) = listener.accept()?;
-
-                let connection_token = io.allocate_token();
-                io.poll.register(&sock, connection_token,
-                                 mio::Ready::readable() | UnixReady::hup(),
-                                 mio::PollOpt::edge())?;
-
-                debug!("data connection established via PASV mode");
-
-                DataTransfer::Connecting {
-                    stream: sock,
-                    token: connection_token,
-                }
-            },
+            // The data connection may have been closed since the event came in.
+            DataTransfer::None => DataTransfer::None,
             DataTransfer::Connecting { stream, token } => {
-                debug!("data connection established via ACTIVE mode");
+                match stream.take_error() {
+                    Ok(None) if readiness.is_writable() => {
+                        debug!("data connection established via ACTIVE mode");
+                        DataTransfer::Connected { stream: data_stream(stream, state, server), token: token }
+                    },
+                    Ok(None) => DataTransfer::Connecting { stream: stream, token: token },
+                    Ok(Some(e)) | Err(e) => {
+                        info!("could not connect to the client for ACTIVE mode: {}", e);
 
-                // If we received an event on a connecting socket,
-                // it must be writable.
-                DataTransfer::Connected { stream: stream, token: token }
-            },
-            DataTransfer::Connected { stream, token } => {
-                DataTransfer::Connected { stream: stream, token: token }
+                        // The transfer can't go ahead without a data connection.
+                        if let Some(transfer) = state.session.expect_ready_mut()?.active_transfer.take() {
+                            transfer.discard(server.file_system_mut());
+                        }
+                        connection.send_reply(protocol::reply::transfer::cannot_connect(&e.to_string()))?;
+                        DataTransfer::None
+                    },
+                }
             },
+            // Listeners only become readable.
+            dtp => dtp,
         }
     }
 
-    if event.readiness().is_readable() {
+    if readiness.is_readable() {
         let dtp = std::mem::replace(&mut connection.dtp, DataTransfer::None);
 
         connection.dtp = match dtp {
-            DataTransfer::None => unreachable!(),
-            DataTransfer::Listening { listener, .. } => {
-                let (sock, _) = listener.accept()?;
-
-                let connection_token = io.allocate_token();
-                io.poll.register(&sock, connection_token,
-                                 mio::Ready::readable() | UnixReady::hup(),
-                                 mio::PollOpt::edge())?;
-
-                DataTransfer::Connected {
-                    stream: sock,
-                    token: connection_token,
+            DataTransfer::Listening { listener, token, waiting_since } => {
+                match accept_passive(&listener, state.peer_ip, server, io)? {
+                    Some((stream, connection_token)) => {
+                        debug!("data connection established via PASV mode");
+
+                        DataTransfer::Connected {
+                            stream: data_stream(stream, state, server),
+                            token: connection_token,
+                        }
+                    },
+                    None => DataTransfer::Listening {
+                        listener: listener,
+                        token: token,
+                        waiting_since: waiting_since,
+                    },
                 }
             },
             dtp => dtp,
         };
     }
 
+    // Clients may start the TLS handshake before sending the transfer
+    // command, so we can't wait for the transfer to do it.
+    let handshake = match connection.dtp {
+        DataTransfer::Connected { ref mut stream, .. } => stream.handshake(),
+        _ => Ok(()),
+    };
+
+    match handshake {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => (),
+        Err(e) => {
+            info!("TLS handshake failed on the data connection: {}", e);
+            connection.dtp = DataTransfer::None;
+
+            if let Session::Ready(ref mut session) = state.session {
+                if let Some(transfer) = session.active_transfer.take() {
+                    transfer.discard(server.file_system_mut());
+                    connection.send_reply(protocol::reply::transfer::connection_lost(&e.to_string()))?;
+                }
+            }
+        },
+        Ok(()) => (),
+    }
+
     Ok(())
 }
 
+/// Accepts the client's passive data connection, if it has been made.
+///
+/// Connections from other hosts are refused, unless the server
+/// allows them, and we carry on waiting for the client.
+fn accept_passive(listener: &TcpListener,
+                  peer_ip: IpAddr,
+                  server: &Server,
+                  io: &mut Io)
+    -> Result<Option<(TcpStream, mio::Token)>, Error> {
+    loop {
+        let (stream, addr) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if server.verify_passive_peer() && unmap_ip(addr.ip()) != peer_ip {
+            // Dropping the stream closes the connection.
+            warn!("refusing passive data connection from {}, expected the client at {}",
+                  addr, peer_ip);
+            continue;
+        }
+
+        let token = io.allocate_token();
+        io.poll.register(&stream, token,
+                         mio::Ready::readable() | UnixReady::hup() |
+                         mio::Ready::writable(),
+                         mio::PollOpt::edge())?;
+
+        return Ok(Some((stream, token)));
+    }
+}
+
+/// Wraps a new data connection, protecting it with TLS
+/// if the client has sent 'PROT P'.
+fn data_stream(socket: TcpStream, state: &ClientState, server: &Server) -> Stream {
+    let mut stream = Stream::new(socket);
+
+    if state.data_protection == ProtectionLevel::Private {
+        if let Some(config) = server.tls_config() {
+            stream.start_tls(&config);
+        }
+    }
+
+    stream
+}
+
+/// Gets the reply to an error from the file system.
+///
+/// Errors caused by the path the client asked for are permanent,
+/// anything else is our fault and might go away if they try again.
+pub fn file_system_error(error: &io::Error) -> protocol::Reply {
+    let code = match error.kind() {
+        io::ErrorKind::NotFound |
+            io::ErrorKind::PermissionDenied |
+            io::ErrorKind::AlreadyExists |
+            io::ErrorKind::IsADirectory |
+            io::ErrorKind::NotADirectory |
+            io::ErrorKind::DirectoryNotEmpty |
+            io::ErrorKind::InvalidInput => protocol::reply::code::REQUESTED_ACTION_NOT_TAKEN,
+        _ => protocol::reply::code::REQUESTED_ACTION_ABORTED_LOCAL_ERROR_IN_PROCESSING,
+    };
+
+    protocol::Reply::new(code, format!("error: {}", error))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use fs::FileSystem;
+    use io::Interpreter;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use net2::TcpBuilder;
+    use std::net;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn ignores_hangups_and_errors_on_the_control_connection() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(&listener.local_addr().unwrap()).unwrap();
+        let (_client, client_addr) = listener.accept().unwrap();
+
+        let token = mio::Token(1);
+        let mut connection = Connection {
+            pi: Interpreter::new(Stream::new(stream), token),
+            dtp: DataTransfer::None,
+        };
+        let mut state = ClientState::new(client_addr.ip());
+        let mut server = TestServer::new();
+        let mut io = Io::new().unwrap();
+
+        let event = mio::Event::new(mio::Ready::from(UnixReady::hup() | UnixReady::error()), token);
+        handle_event(&mut state, &event, &mut connection, token, &mut server, &mut io).unwrap();
+    }
+
+    #[test]
+    fn refused_unique_uploads_do_not_leave_files_behind() {
+        let mut server = TestServer::new().with_tls();
+        server.requires_tls = true;
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.start_tls();
+        control.login();
+        assert_eq!(control.command("PBSZ 0").code.0, 200);
+        assert_eq!(control.command("STOU").code.0, 521);
+
+        let server = server.stop();
+        assert_eq!(server.file_system.list(Path::new("/")).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn advertises_the_passive_address() {
+        let mut server = TestServer::new();
+        server.passive_address = Some("203.0.113.7".parse().unwrap());
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        let reply = control.command("PASV");
+        assert_eq!(reply.code.0, 227);
+        assert!(reply.text.to_string().contains("(203,0,113,7,"));
+    }
+
+    #[test]
+    fn refuses_passive_connections_from_other_hosts() {
+        let mut server = TestServer::new();
+        server.file_system.write_file(Path::new("/hello.txt"), b"hello".to_vec()).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        let address = control.epsv();
+
+        // Another host on the loopback network gets there first.
+        let builder = TcpBuilder::new_v4().unwrap();
+        builder.bind("127.0.0.2:0").unwrap();
+        let mut intruder = builder.connect(address).unwrap();
+        intruder.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut data = net::TcpStream::connect(address).unwrap();
+        control.start_transfer("RETR hello.txt");
+
+        let mut received = Vec::new();
+        data.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"hello");
+        assert_eq!(control.reply().code.0, 226);
+
+        // We hung up on the other host.
+        let mut buffer = [0; 16];
+        match intruder.read(&mut buffer) {
+            Ok(0) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => (),
+            result => panic!("connection from another host was not closed: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn refuses_to_remove_directories_that_are_not_empty() {
+        let mut server = TestServer::new();
+        server.file_system.create_dir(Path::new("/docs")).unwrap();
+        server.file_system.write_file(Path::new("/docs/hello.txt"), b"hello".to_vec()).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+        assert_eq!(control.command("RMD docs").code.0, 550);
+        assert_eq!(control.command("RMD docs/hello.txt").code.0, 550);
+    }
+
+    #[test]
+    fn refuses_to_delete_directories() {
+        let mut server = TestServer::new();
+        server.file_system.create_dir(Path::new("/docs")).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+        assert_eq!(control.command("DELE docs").code.0, 550);
+    }
+
+    #[test]
+    fn refuses_to_move_directories_inside_themselves() {
+        let mut server = TestServer::new();
+        server.file_system.create_dir(Path::new("/docs")).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+        assert_eq!(control.command("RNFR docs").code.0, 350);
+        assert_eq!(control.command("RNTO docs/old").code.0, 550);
+    }
+}
@@ -0,0 +1,48 @@
+use Error;
+use server::client::{ClientState, Action};
+
+/// Handle the 'ABOR' command.
+pub fn handle(client: &mut ClientState) -> Result<Action, Error> {
+    let session = client.session.expect_ready_mut()?;
+
+    // Anything we have received of a new file is thrown away
+    // along with the transfer.
+    Ok(Action::Abort { transfer: session.active_transfer.take() })
+}
+
+#[cfg(test)]
+mod test
+{
+    use fs::FileSystem;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use std::path::Path;
+
+    #[test]
+    fn aborts_transfers() {
+        // Far more than fits in the socket buffers, so the
+        // transfer cannot finish while nobody is reading it.
+        let mut server = TestServer::new();
+        server.file_system.write_file(Path::new("/big.bin"), vec![0; 32 * 1024 * 1024]).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        let mut data = control.passive();
+        control.start_transfer("RETR big.bin");
+
+        assert_eq!(control.command("ABOR").code.0, 426);
+        assert_eq!(control.reply().code.0, 226);
+        assert!(data.read_all().len() < 32 * 1024 * 1024);
+    }
+
+    #[test]
+    fn replies_when_there_is_no_transfer() {
+        let server = TestServer::new().start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+        assert_eq!(control.command("ABOR").code.0, 225);
+    }
+}
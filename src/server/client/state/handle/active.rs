@@ -1,17 +1,53 @@
 use {Error, protocol};
 use server::client::{ClientState, Action};
+use server::Server;
+use io::DataTransferMode;
+
+use std::net::SocketAddr;
 
 /// Handle the 'PORT' command.
 pub fn handle_port(port: &protocol::PORT,
-                   client: &mut ClientState)
+                   client: &mut ClientState,
+                   server: &mut Server)
     -> Result<Action, Error> {
-    let mut session = client.session.expect_ready_mut()?;
+    use_client_addr(port.to_socket_addr(), protocol::reply::port::success(), client, server)
+}
+
+/// Handle the 'EPRT' command.
+pub fn handle_eprt(eprt: &protocol::EPRT,
+                   client: &mut ClientState,
+                   server: &mut Server)
+    -> Result<Action, Error> {
+    use_client_addr(eprt.to_socket_addr(), protocol::reply::eprt::success(), client, server)
+}
+
+/// Switches to active mode, connecting to the given address,
+/// if the server allows it.
+fn use_client_addr(addr: SocketAddr,
+                   success: protocol::Reply,
+                   client: &mut ClientState,
+                   server: &mut Server)
+    -> Result<Action, Error> {
+    let peer_ip = client.peer_ip;
+    let session = client.session.expect_ready_mut()?;
+
+    if session.epsv_all {
+        return Err(super::only_epsv_allowed());
+    }
+
+    // Otherwise the client could have us connect anywhere for them.
+    let policy = server.data_address_policy(&session.credentials);
+    if let Err(reason) = policy.check(&addr, peer_ip) {
+        info!("refusing active DTP connection to {} for client at {}: {}", addr, peer_ip, reason);
+        return Ok(Action::Reply(protocol::reply::transfer::address_not_allowed(reason)));
+    }
 
-    debug!("client requested we initiate an active DTP connection on port {}", port.port);
+    debug!("client requested we initiate an active DTP connection to {}", addr);
 
     // For active mode, we set the socket address on the session so that
     // we keep the address for later use. We do not have to worry in passive
     // mode because the client always initiates the data connection.
-    session.client_addr = Some(port.to_socket_addr());
-    Ok(Action::Reply(protocol::Reply::new(protocol::reply::code::OK, "port")))
+    session.client_addr = Some(addr);
+    session.data_transfer_mode = DataTransferMode::Active;
+    Ok(Action::Reply(success))
 }
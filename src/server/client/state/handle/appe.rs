@@ -0,0 +1,41 @@
+use {Error, server, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+
+/// Handle the 'APPE' command.
+pub fn handle(appe: &protocol::APPE,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready_mut()?;
+
+    let path = session.resolve_path(&appe.remote_filename);
+    debug!("client is appending to {}", path.display());
+
+    let mode = match session.restart_marker.take() {
+        Some(0) | None => server::StoreMode::Append,
+        Some(offset) => {
+            let file_size = server.file_system().metadata(&path)?.size;
+            if offset > file_size {
+                return Ok(Action::Reply(protocol::reply::rest::invalid_marker(file_size)));
+            }
+
+            server::StoreMode::Restart { offset: offset }
+        },
+    };
+
+    // The file is opened once the client connects, but we can
+    // tell them about a missing directory straight away.
+    if let Some(parent) = path.parent() {
+        server.file_system().metadata(parent)?;
+    }
+
+    Ok(Action::Transfer(server::Transfer {
+        file_type: session.transfer_type,
+        direction: server::Direction::Incoming {
+            path: path,
+            mode: mode,
+            file: None,
+        },
+    }))
+}
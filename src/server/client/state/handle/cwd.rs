@@ -1,11 +1,90 @@
 use {Error, protocol};
+use fs::FileKind;
+use server::Server;
 use server::client::{ClientState, Action};
 
 /// Handle the 'CWD' command.
 pub fn handle(cwd: &protocol::CWD,
-              client: &mut ClientState) -> Result<Action, Error> {
+              client: &mut ClientState,
+              server: &mut Server) -> Result<Action, Error> {
     let mut session = client.session.expect_ready_mut()?;
 
-    session.working_dir = cwd.path.clone().into();
+    let path = session.resolve_path(&cwd.path);
+    if server.file_system().metadata(&path)?.kind != FileKind::Directory {
+        return Ok(Action::Reply(protocol::reply::cwd::not_a_directory()));
+    }
+
+    session.working_dir = path;
     Ok(Action::Reply(protocol::reply::cwd::success()))
 }
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use Credentials;
+    use fs::{self, FileSystem};
+    use server::client::Session;
+    use server::client::state::session::Ready;
+    use std::path::{Path, PathBuf};
+
+    struct TestServer
+    {
+        file_system: fs::Memory,
+    }
+
+    impl Server for TestServer
+    {
+        fn welcome_message(&self) -> String { "test server".to_owned() }
+
+        fn file_system(&self) -> &FileSystem { &self.file_system }
+        fn file_system_mut(&mut self) -> &mut FileSystem { &mut self.file_system }
+    }
+
+    fn setup() -> (ClientState, TestServer) {
+        let mut file_system = fs::Memory::new();
+        file_system.create_dir(Path::new("/docs")).unwrap();
+        file_system.create_dir(Path::new("/docs/old")).unwrap();
+        file_system.write_file(Path::new("/docs/hello.txt"), b"hello world".to_vec()).unwrap();
+
+        let mut client = ClientState::new("127.0.0.1".parse().unwrap());
+        client.session = Session::Ready(Ready::new(Credentials { username: "bob".to_owned(), password: None }));
+
+        (client, TestServer { file_system: file_system })
+    }
+
+    fn cwd(client: &mut ClientState, server: &mut TestServer, path: &str) -> Result<protocol::Reply, Error> {
+        match handle(&protocol::CWD { path: path.to_owned() }, client, server)? {
+            Action::Reply(reply) => Ok(reply),
+            action => panic!("unexpected action: {:?}", action),
+        }
+    }
+
+    fn working_dir(client: &ClientState) -> PathBuf {
+        client.session.expect_ready().unwrap().working_dir.clone()
+    }
+
+    #[test]
+    fn correctly_resolves_relative_paths() {
+        let (mut client, mut server) = setup();
+
+        cwd(&mut client, &mut server, "docs").unwrap();
+        cwd(&mut client, &mut server, "old").unwrap();
+        assert_eq!(working_dir(&client), PathBuf::from("/docs/old"));
+
+        cwd(&mut client, &mut server, "..").unwrap();
+        assert_eq!(working_dir(&client), PathBuf::from("/docs"));
+    }
+
+    #[test]
+    fn only_changes_to_directories() {
+        let (mut client, mut server) = setup();
+        cwd(&mut client, &mut server, "/docs").unwrap();
+
+        let reply = cwd(&mut client, &mut server, "hello.txt").unwrap();
+        assert_eq!(reply.code, protocol::reply::code::REQUESTED_ACTION_NOT_TAKEN);
+        assert!(cwd(&mut client, &mut server, "missing").is_err());
+
+        assert_eq!(working_dir(&client), PathBuf::from("/docs"));
+    }
+}
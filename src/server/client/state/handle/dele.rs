@@ -0,0 +1,16 @@
+use {Error, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+
+/// Handle the 'DELE' command.
+pub fn handle(dele: &protocol::DELE,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let path = session.resolve_path(&dele.remote_filename);
+    server.file_system_mut().remove_file(&path)?;
+
+    Ok(Action::Reply(protocol::reply::dele::success()))
+}
@@ -1,7 +1,27 @@
-use {Error, protocol};
-use server::client::Action;
+use Error;
+use server::client::{ClientState, Action};
+use server::Server;
+use protocol::reply::feat::{Features, Feature};
+use protocol::facts::FactKind;
 
 /// Handle the 'FEAT' command.
-pub fn handle() -> Result<Action, Error> {
-    Ok(Action::Reply(protocol::reply::feat::Features::default().into()))
+pub fn handle(client: &mut ClientState, server: &mut Server) -> Result<Action, Error> {
+    let mut features = vec![
+        Feature { name: "EPRT".to_owned() },
+        Feature { name: "EPSV".to_owned() },
+        Feature { name: "MDTM".to_owned() },
+        Feature { name: FactKind::feature_string(&client.mlst_facts) },
+        Feature { name: "REST STREAM".to_owned() },
+        Feature { name: "SIZE".to_owned() },
+    ];
+
+    if server.tls_config().is_some() {
+        features.push(Feature { name: "AUTH TLS".to_owned() });
+        features.push(Feature { name: "PBSZ".to_owned() });
+        features.push(Feature { name: "PROT".to_owned() });
+    }
+
+    let features = Features::new(features);
+
+    Ok(Action::Reply(features.into()))
 }
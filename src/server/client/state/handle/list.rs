@@ -1,24 +1,113 @@
 use {Error, FileType, server, protocol};
+use fs::DirEntry;
 use server::Server;
+use server::listing;
 use server::client::{ClientState, Action};
 
+use std::time::SystemTime;
+
 /// Handle the 'LIST' command.
 pub fn handle(list: &protocol::LIST,
               client: &mut ClientState,
               server: &mut Server)
     -> Result<Action, Error> {
-    if list.remote_filespec.is_some() {
-        unimplemented!();
-    }
+    let session = client.session.expect_ready()?;
+
+    let arguments = Arguments::parse(list.remote_filespec.as_ref().map(|s| &s[..]).unwrap_or(""));
+    let path = session.resolve_path(arguments.path.unwrap_or("."));
+
+    let metadata = server.file_system().metadata(&path)?;
 
-    let working_dir = client.session.expect_ready()?.working_dir.clone();
+    let entries = if metadata.is_dir() {
+        let mut entries = server.file_system().list_entries(&path)?;
+        if !arguments.show_hidden {
+            entries.retain(|entry| !entry.name.starts_with('.'));
+        }
 
-    let entries = server.file_system().list(&working_dir)?;
-    let mut data: String = entries.join("\r\n");
-    data.extend("\r\n".chars());
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    } else {
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        vec![DirEntry { name: name.unwrap_or_default(), metadata: metadata }]
+    };
+
+    let now = SystemTime::now();
+    let mut data = String::new();
+    for entry in entries {
+        data.push_str(&listing::long_format(&entry, now));
+        data.push_str("\r\n");
+    }
 
     Ok(Action::Transfer(server::Transfer {
         file_type: FileType::ascii(),
-        data: data.as_bytes().to_owned(),
+        direction: server::Direction::outgoing_data(data.into_bytes()),
     }))
 }
+
+/// The `ls` options we understand.
+const KNOWN_OPTIONS: &'static str = "aAl";
+
+/// The arguments to a 'LIST' command.
+///
+/// Many clients send `ls` style options such as `-la`, even though
+/// they aren't part of the standard.
+struct Arguments<'a>
+{
+    /// Whether to show files starting with a dot ('-a').
+    show_hidden: bool,
+    /// The file or directory to list.
+    path: Option<&'a str>,
+}
+
+impl<'a> Arguments<'a>
+{
+    fn parse(mut text: &'a str) -> Self {
+        let mut show_hidden = false;
+
+        loop {
+            text = text.trim_start();
+            if !text.starts_with('-') { break; }
+
+            let option_end = text.find(char::is_whitespace).unwrap_or(text.len());
+            let (options, rest) = text.split_at(option_end);
+
+            // Anything else starting with a dash is a file name.
+            if options.len() == 1 || !options[1..].chars().all(|c| KNOWN_OPTIONS.contains(c)) { break; }
+
+            // We always give a long listing, so '-l' is a no-op.
+            show_hidden |= options.contains('a') || options.contains('A');
+            text = rest;
+        }
+
+        Arguments {
+            show_hidden: show_hidden,
+            path: if text.is_empty() { None } else { Some(text) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::Arguments;
+
+    #[test]
+    fn correctly_parses_options() {
+        let arguments = Arguments::parse("-la docs");
+        assert!(arguments.show_hidden);
+        assert_eq!(arguments.path, Some("docs"));
+
+        let arguments = Arguments::parse("-l");
+        assert!(!arguments.show_hidden);
+        assert_eq!(arguments.path, None);
+    }
+
+    #[test]
+    fn treats_unknown_options_as_paths() {
+        let arguments = Arguments::parse("-myfile");
+        assert!(!arguments.show_hidden);
+        assert_eq!(arguments.path, Some("-myfile"));
+
+        assert_eq!(Arguments::parse("-a -").path, Some("-"));
+    }
+}
@@ -0,0 +1,65 @@
+use {Error, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+use protocol::time::DateTime;
+
+/// Handle the 'MDTM' command.
+pub fn handle(mdtm: &protocol::MDTM,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let path = session.resolve_path(&mdtm.remote_filename);
+    let metadata = server.file_system().metadata(&path)?;
+
+    if !metadata.is_file() {
+        return Ok(Action::Reply(protocol::reply::mdtm::not_a_file()));
+    }
+
+    let modified = DateTime::from_system_time(metadata.modified).timeval();
+    Ok(Action::Reply(protocol::reply::mdtm::success(&modified)))
+}
+
+#[cfg(test)]
+mod test
+{
+    use fs::FileSystem;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use protocol::time::DateTime;
+    use std::path::Path;
+
+    #[test]
+    fn gives_modification_times_in_utc() {
+        let mut server = TestServer::new();
+        server.file_system.write_file(Path::new("/hello.txt"), b"hello".to_vec()).unwrap();
+        let modified = server.file_system.metadata(Path::new("/hello.txt")).unwrap().modified;
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        let reply = control.command("MDTM hello.txt");
+        assert_eq!(reply.code.0, 213);
+
+        // YYYYMMDDHHMMSS
+        let text = reply.text.to_string();
+        assert_eq!(text.len(), 14);
+        assert!(text.bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(DateTime::parse_timeval(&text), Some(DateTime::from_system_time(modified)));
+    }
+
+    #[test]
+    fn refuses_missing_files_and_directories() {
+        let mut server = TestServer::new();
+        server.file_system.create_dir(Path::new("/docs")).unwrap();
+        let server = server.start(ListenerMode::Plain);
+
+        let mut control = server.connect();
+        control.login();
+
+        assert_eq!(control.command("MDTM missing.txt").code.0, 550);
+        assert_eq!(control.command("MDTM docs").code.0, 550);
+    }
+}
@@ -1,9 +1,7 @@
-use {Error, ErrorKind, protocol};
+use {Error, protocol};
 use server::Server;
 use server::client::{ClientState, Action};
 
-use std::path::Path;
-
 /// Handle the 'MKD' command.
 pub fn handle(mkd: &protocol::MKD,
               client: &mut ClientState,
@@ -11,21 +9,9 @@ pub fn handle(mkd: &protocol::MKD,
 -> Result<Action, Error> {
     let session = client.session.expect_ready()?;
 
-    let path = Path::new(&mkd.remote_filename);
-
-    let path = if path.has_root() {
-        path.to_owned()
-    } else {
-        session.working_dir.join(path)
-    };
+    let path = session.resolve_path(&mkd.remote_filename);
 
-    match server.file_system_mut().create_dir(&path) {
-        Ok(..) => Ok(Action::Reply(protocol::reply::mkd::success())),
-        // IO errors are caused by the client, not us.
-        Err(Error(ErrorKind::Io(..), _)) => {
-            unimplemented!();
-        },
-        Err(e) => Err(e),
-    }
+    server.file_system_mut().create_dir(&path)?;
+    Ok(Action::Reply(protocol::reply::mkd::success()))
 }
 
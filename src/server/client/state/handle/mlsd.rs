@@ -0,0 +1,43 @@
+use {Error, FileType, server, protocol};
+use fs::DirEntry;
+use server::Server;
+use server::listing;
+use server::client::{ClientState, Action};
+use protocol::facts::EntryType;
+
+/// Handle the 'MLSD' command.
+pub fn handle(mlsd: &protocol::MLSD,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let path = session.resolve_path(mlsd.remote_directory.as_ref().map(|s| &s[..]).unwrap_or("."));
+    let metadata = server.file_system().metadata(&path)?;
+
+    if !metadata.is_dir() {
+        return Ok(Action::Reply(protocol::reply::mlsd::not_a_directory()));
+    }
+
+    // The listing starts with the directory itself.
+    let mut current_dir = listing::facts_entry(&DirEntry { name: ".".to_owned(), metadata: metadata }, &path);
+    current_dir.facts.entry_type = Some(EntryType::CurrentDir);
+
+    let mut entries = server.file_system().list_entries(&path)?;
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut data = current_dir.to_line(&client.mlst_facts);
+    data.push_str("\r\n");
+
+    for entry in entries {
+        let facts_entry = listing::facts_entry(&entry, &path.join(&entry.name));
+
+        data.push_str(&facts_entry.to_line(&client.mlst_facts));
+        data.push_str("\r\n");
+    }
+
+    Ok(Action::Transfer(server::Transfer {
+        file_type: FileType::ascii(),
+        direction: server::Direction::outgoing_data(data.into_bytes()),
+    }))
+}
@@ -0,0 +1,21 @@
+use {Error, protocol};
+use fs::DirEntry;
+use server::Server;
+use server::listing;
+use server::client::{ClientState, Action};
+
+/// Handle the 'MLST' command.
+pub fn handle(mlst: &protocol::MLST,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let path = session.resolve_path(mlst.remote_filespec.as_ref().map(|s| &s[..]).unwrap_or("."));
+    let metadata = server.file_system().metadata(&path)?;
+
+    let entry = DirEntry { name: path.display().to_string(), metadata: metadata };
+    let facts_entry = listing::facts_entry(&entry, &path);
+
+    Ok(Action::Reply(protocol::reply::mlst::success(&facts_entry, &client.mlst_facts)))
+}
@@ -6,6 +6,14 @@ mod pwd;
 mod cwd;
 mod cdup;
 mod list;
+mod nlst;
+mod mlsd;
+mod mlst;
+mod opts;
+mod size;
+mod mdtm;
+mod rest;
+mod abor;
 mod syst;
 mod feat;
 mod ty;
@@ -13,10 +21,18 @@ mod passive;
 mod active;
 mod quit;
 mod retr;
+mod stor;
+mod appe;
+mod stou;
+mod dele;
+mod rmd;
+mod rnfr;
+mod rnto;
+mod security;
 mod mkd;
 
 use Error;
-use server::client::{ClientState, Action};
+use server::client::{ClientState, Action, Session};
 use server::Server;
 use protocol;
 
@@ -28,73 +44,94 @@ pub fn command(client: &mut ClientState,
 
     debug!("received command: {:?}", command);
 
+    if let Session::Ready(ref mut session) = client.session {
+        // A pending rename is only valid for the command directly after 'RNFR'.
+        if let RNTO(..) = *command { } else {
+            session.rename_from = None;
+        }
+
+        // Likewise, a restart marker only applies to the transfer
+        // directly after 'REST'.
+        match *command {
+            REST(..) | RETR(..) | STOR(..) | APPE(..) => (),
+            _ => session.restart_marker = None,
+        }
+    }
+
     match *command {
         // User attempting to log in.
         USER(ref user) => self::user::handle(user, client, server),
         PASS(ref pass) => self::pass::handle(pass, client, server),
         PWD(..) => self::pwd::handle(client),
-        CWD(ref cwd) => self::cwd::handle(cwd, client),
+        CWD(ref cwd) => self::cwd::handle(cwd, client, server),
         CDUP(..) => self::cdup::handle(client),
         MKD(ref mkd) => self::mkd::handle(mkd, client, server),
         LIST(ref list) => self::list::handle(list, client, server),
+        NLST(ref nlst) => self::nlst::handle(nlst, client, server),
+        MLSD(ref mlsd) => self::mlsd::handle(mlsd, client, server),
+        MLST(ref mlst) => self::mlst::handle(mlst, client, server),
+        SIZE(ref size) => self::size::handle(size, client, server),
+        MDTM(ref mdtm) => self::mdtm::handle(mdtm, client, server),
         // ClientState requesting information about the server system.
         SYST(..) => self::syst::handle(),
-        FEAT(..) => self::feat::handle(),
+        FEAT(..) => self::feat::handle(client, server),
+        OPTS(ref opts) => self::opts::handle(opts, client),
         TYPE(ref ty) => self::ty::handle(ty, client),
         PASV(..) => self::passive::handle_pasv(client),
-        EPSV(..) => self::passive::handle_epsv(client),
-        PORT(ref port) => self::active::handle_port(port, client),
+        EPSV(ref epsv) => self::passive::handle_epsv(epsv, client),
+        PORT(ref port) => self::active::handle_port(port, client, server),
+        EPRT(ref eprt) => self::active::handle_eprt(eprt, client, server),
         QUIT(..) => self::quit::handle(),
+        ABOR(..) => self::abor::handle(client),
+        REST(ref rest) => self::rest::handle(rest, client),
         RETR(ref retr) => self::retr::handle(retr, client, server),
-        EPRT(..) => self::unimplemented("EPRT"),
-        ABOR(..) => self::unimplemented("ABOR"),
+        STOR(ref stor) => self::stor::handle(stor, client, server),
+        APPE(ref appe) => self::appe::handle(appe, client, server),
+        STOU(..) => self::stou::handle(client, server),
+        DELE(ref dele) => self::dele::handle(dele, client, server),
+        RMD(ref rmd) => self::rmd::handle(&rmd.remote_filename, client, server),
+        XRMD(ref xrmd) => self::rmd::handle(&xrmd.remote_filename, client, server),
+        RNFR(ref rnfr) => self::rnfr::handle(rnfr, client, server),
+        RNTO(ref rnto) => self::rnto::handle(rnto, client, server),
+        AUTH(ref auth) => self::security::handle_auth(auth, client, server),
+        PBSZ(ref pbsz) => self::security::handle_pbsz(pbsz, client),
+        PROT(ref prot) => self::security::handle_prot(prot, client),
+        CCC(..) => self::security::handle_ccc(client),
         ACCT(..) => self::unimplemented("ACCT"),
         ADAT(..) => self::unimplemented("ADAT"),
         ALLO(..) => self::unimplemented("ALLO"),
-        APPE(..) => self::unimplemented("APPE"),
-        AUTH(..) => self::unimplemented("AUTH"),
-        CCC(..) => self::unimplemented("CCC"),
         CONF(..) => self::unimplemented("CONF"),
-        DELE(..) => self::unimplemented("DELE"),
         ENC(..) => self::unimplemented("ENC"),
         HELP(..) => self::unimplemented("HELP"),
         HOST(..) => self::unimplemented("HOST"),
         LANG(..) => self::unimplemented("LANG"),
         LPRT(..) => self::unimplemented("LPRT"),
         LPSV(..) => self::unimplemented("LPSV"),
-        MDTM(..) => self::unimplemented("MDTM"),
         MIC(..) => self::unimplemented("MIC"),
-        MLSD(..) => self::unimplemented("MLSD"),
-        MLST(..) => self::unimplemented("MLST"),
         MODE(..) => self::unimplemented("MODE"),
-        NLST(..) => self::unimplemented("NLST"),
         NOOP(..) => self::unimplemented("NOOP"),
-        OPTS(..) => self::unimplemented("OPTS"),
-        PBSZ(..) => self::unimplemented("PBSZ"),
-        PROT(..) => self::unimplemented("PROT"),
         REIN(..) => self::unimplemented("REIN"),
-        REST(..) => self::unimplemented("REST"),
-        RMD(..) => self::unimplemented("RMD"),
-        RNFR(..) => self::unimplemented("RNFR"),
-        RNTO(..) => self::unimplemented("RNTO"),
         SITE(..) => self::unimplemented("SITE"),
-        SIZE(..) => self::unimplemented("SIZE"),
         SMNT(..) => self::unimplemented("SMNT"),
         STAT(..) => self::unimplemented("STAT"),
-        STOR(..) => self::unimplemented("STOR"),
-        STOU(..) => self::unimplemented("STOU"),
         STRU(..) => self::unimplemented("STRU"),
         XCUP(..) => self::unimplemented("XCUP"),
         XMKD(..) => self::unimplemented("XMKD"),
         XPWD(..) => self::unimplemented("XPWD"),
         XRCP(..) => self::unimplemented("XRCP"),
-        XRMD(..) => self::unimplemented("XRMD"),
         XRSQ(..) => self::unimplemented("XRSQ"),
         XSEM(..) => self::unimplemented("XSEM"),
         XSEN(..) => self::unimplemented("XSEN"),
     }
 }
 
+/// Generate the error for a data connection command sent after 'EPSV ALL'.
+fn only_epsv_allowed() -> Error {
+    protocol::Error::from_kind(protocol::ErrorKind::InvalidCommandSequence(
+        "only EPSV may be used after EPSV ALL".to_owned(),
+    )).into()
+}
+
 /// Generate a reply for an unimplemented command.
 fn unimplemented(command_name: &'static str) -> Result<Action, Error> {
     Err(protocol::Error::from_kind(protocol::ErrorKind::UnimplementedCommand(
@@ -0,0 +1,69 @@
+use {Error, FileType, server, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+use util::glob;
+
+use std::path::Path;
+
+/// Handle the 'NLST' command.
+pub fn handle(nlst: &protocol::NLST,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let argument = nlst.remote_directory.as_ref().map(|s| s.trim()).unwrap_or("");
+    let argument_path = Path::new(argument);
+
+    // Wildcards are only supported in the last part of the path.
+    let pattern = match argument_path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if glob::is_pattern(name) => Some(name),
+        _ => None,
+    };
+
+    let names = if let Some(pattern) = pattern {
+        let dir = argument_path.parent().unwrap_or(Path::new(""));
+        let names: Vec<_> = server.file_system().list(&session.resolve_path(dir))?
+            .into_iter()
+            .filter(|name| is_visible(name, pattern) && glob::matches(pattern, name))
+            .map(|name| dir.join(name))
+            .collect();
+
+        if names.is_empty() {
+            return Ok(Action::Reply(protocol::reply::nlst::no_matches()));
+        }
+        names
+    } else {
+        let path = session.resolve_path(argument_path);
+
+        if server.file_system().metadata(&path)?.is_dir() {
+            server.file_system().list(&path)?
+                .into_iter()
+                .filter(|name| !name.starts_with('.'))
+                .map(|name| argument_path.join(name))
+                .collect()
+        } else {
+            vec![argument_path.to_owned()]
+        }
+    };
+
+    let mut names: Vec<String> = names.iter().map(|name| name.display().to_string()).collect();
+    names.sort();
+
+    let mut data = String::new();
+    for name in names {
+        data.push_str(&name);
+        data.push_str("\r\n");
+    }
+
+    Ok(Action::Transfer(server::Transfer {
+        file_type: FileType::ascii(),
+        direction: server::Direction::outgoing_data(data.into_bytes()),
+    }))
+}
+
+/// Checks if a file should be shown, hiding dotfiles unless the
+/// pattern explicitly asks for them like a shell would.
+fn is_visible(name: &str, pattern: &str) -> bool {
+    !name.starts_with('.') || pattern.starts_with('.')
+}
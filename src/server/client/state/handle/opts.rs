@@ -0,0 +1,18 @@
+use {Error, protocol};
+use server::client::{ClientState, Action};
+use protocol::facts::FactKind;
+
+/// Handle the 'OPTS' command.
+pub fn handle(opts: &protocol::OPTS,
+              client: &mut ClientState)
+    -> Result<Action, Error> {
+    if opts.command_name.eq_ignore_ascii_case("MLST") {
+        let facts = opts.options.as_ref().map(|options| FactKind::parse_list(options));
+        client.mlst_facts = facts.unwrap_or_default();
+
+        let facts = FactKind::list_to_string(&client.mlst_facts);
+        Ok(Action::Reply(protocol::reply::opts::mlst(&facts)))
+    } else {
+        Ok(Action::Reply(protocol::reply::opts::unsupported(&opts.command_name)))
+    }
+}
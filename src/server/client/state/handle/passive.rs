@@ -1,31 +1,33 @@
 use {Error, protocol};
 use server::client::{ClientState, Action};
-use io::DataTransferMode;
 
 pub fn handle_pasv(client: &mut ClientState)
     -> Result<Action, Error> {
-    let port = listen_passive_dtp(client)?;
-    Ok(Action::EstablishDataConnection {
-        reply: protocol::reply::pasv::success(port),
-        mode: DataTransferMode::Passive { port: port }
-    })
-}
+    let session = client.session.expect_ready()?;
 
-pub fn handle_epsv(client: &mut ClientState)
-    -> Result<Action, Error> {
-    let port = listen_passive_dtp(client)?;
-    Ok(Action::EstablishDataConnection {
-        reply: protocol::reply::epsv::success(port),
-        mode: DataTransferMode::Passive { port: port }
-    })
+    if session.epsv_all {
+        return Err(super::only_epsv_allowed());
+    }
+
+    Ok(Action::ListenPassive { extended: false, network_protocol: None })
 }
 
-/// Attempts to open a data connection passively.
-fn listen_passive_dtp(client: &mut ClientState)
-    -> Result<u16, Error> {
-    let mut session = client.session.expect_ready_mut()?;
-    let port = 5166;
+pub fn handle_epsv(epsv: &protocol::EPSV,
+                   client: &mut ClientState)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready_mut()?;
 
-    session.data_transfer_mode = DataTransferMode::Passive { port: port };
-    Ok(port)
+    match *epsv {
+        protocol::EPSV::Any => Ok(Action::ListenPassive { extended: true, network_protocol: None }),
+        protocol::EPSV::Protocol(network_protocol) => Ok(Action::ListenPassive {
+            extended: true,
+            network_protocol: Some(network_protocol),
+        }),
+        protocol::EPSV::All => {
+            // NATs and firewalls can rely on the client never
+            // sending another address after this.
+            session.epsv_all = true;
+            Ok(Action::Reply(protocol::reply::epsv::all()))
+        },
+    }
 }
@@ -0,0 +1,14 @@
+use {Error, protocol};
+use server::client::{ClientState, Action};
+
+/// Handle the 'REST' command.
+pub fn handle(rest: &protocol::REST,
+              client: &mut ClientState)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready_mut()?;
+
+    // The marker is checked against the file once we know which file
+    // is being transferred.
+    session.restart_marker = Some(rest.marker);
+    Ok(Action::Reply(protocol::reply::rest::success(rest.marker)))
+}
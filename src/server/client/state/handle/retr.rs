@@ -1,19 +1,25 @@
 use {Error, FileType, server, protocol};
 use server::Server;
 use server::client::{ClientState, Action};
-use std::path::Path;
 
 /// Handle the 'RETR' command.
 pub fn handle(retr: &protocol::RETR,
               client: &mut ClientState,
               server: &mut Server)
     -> Result<Action, Error> {
-    client.session.expect_ready()?;
+    let session = client.session.expect_ready_mut()?;
+    let restart_marker = session.restart_marker.take().unwrap_or(0);
 
-    let data = server.file_system().read_file(&Path::new(&retr.remote_filename))?;
+    let path = session.resolve_path(&retr.remote_filename);
+    let file_size = server.file_system().metadata(&path)?.size;
+
+    if restart_marker > file_size {
+        return Ok(Action::Reply(protocol::reply::rest::invalid_marker(file_size)));
+    }
+    let reader = server.file_system().open_reader(&path, restart_marker)?;
 
     Ok(Action::Transfer(server::Transfer {
         file_type: FileType::ascii(),
-        data: data,
+        direction: server::Direction::outgoing(reader),
     }))
 }
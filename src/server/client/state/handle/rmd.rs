@@ -0,0 +1,16 @@
+use {Error, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+
+/// Handle the 'RMD' and 'XRMD' commands.
+pub fn handle(remote_filename: &str,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let path = session.resolve_path(remote_filename);
+    server.file_system_mut().remove_dir(&path)?;
+
+    Ok(Action::Reply(protocol::reply::rmd::success()))
+}
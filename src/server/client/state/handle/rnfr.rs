@@ -0,0 +1,36 @@
+use {Error, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+
+use std::io;
+
+/// Handle the 'RNFR' command.
+pub fn handle(rnfr: &protocol::RNFR,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready_mut()?;
+
+    let path = session.resolve_path(&rnfr.from_filename);
+
+    // Make sure the file exists before we ask for the destination.
+    //
+    // We look for it in its directory rather than asking for its
+    // metadata, which follows symbolic links. Renaming a link only
+    // touches the link, so one pointing nowhere can still be renamed.
+    let exists = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => {
+            let names = server.file_system().list(parent)?;
+            names.iter().any(|name| file_name == name.as_str())
+        },
+        _ => false,
+    };
+
+    if !exists {
+        return Err(io::Error::new(io::ErrorKind::NotFound,
+            format!("file does not exist: {}", path.display())).into());
+    }
+
+    session.rename_from = Some(path);
+    Ok(Action::Reply(protocol::reply::rnfr::ready()))
+}
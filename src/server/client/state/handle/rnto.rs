@@ -0,0 +1,21 @@
+use {Error, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+
+/// Handle the 'RNTO' command.
+pub fn handle(rnto: &protocol::RNTO,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready_mut()?;
+
+    let from = match session.rename_from.take() {
+        Some(from) => from,
+        None => return Ok(Action::Reply(protocol::reply::rnto::no_source())),
+    };
+
+    let to = session.resolve_path(&rnto.to_filename);
+    server.file_system_mut().rename(&from, &to)?;
+
+    Ok(Action::Reply(protocol::reply::rnto::success()))
+}
@@ -0,0 +1,148 @@
+use {Error, protocol};
+use server::client::{ClientState, Action, Session};
+use server::client::state::session;
+use server::Server;
+use protocol::ProtectionLevel;
+
+/// Handle the 'AUTH' command.
+pub fn handle_auth(auth: &protocol::AUTH,
+                   client: &mut ClientState,
+                   server: &mut Server)
+    -> Result<Action, Error> {
+    if client.secure {
+        return Err(protocol::Error::from_kind(protocol::ErrorKind::InvalidCommandSequence(
+            "the control connection is already protected".to_owned(),
+        )).into());
+    }
+
+    // 'SSL' and 'TLS-P' come from drafts of RFC 4217, where they
+    // also protected the data connections.
+    let data_protection = match &auth.auth_type.trim().to_uppercase()[..] {
+        "TLS" | "TLS-C" => ProtectionLevel::Clear,
+        "SSL" | "TLS-P" => ProtectionLevel::Private,
+        mechanism => return Ok(Action::Reply(protocol::reply::auth::unsupported_mechanism(mechanism))),
+    };
+
+    if server.tls_config().is_none() {
+        return Ok(Action::Reply(protocol::reply::auth::unavailable()));
+    }
+
+    // RFC 2228 has the user log in again once the connection is
+    // protected, even if they had already logged in. Anything they
+    // did before then was in plain text, so could have been tampered
+    // with, and the server may want to treat TLS users differently.
+    client.session = Session::Login(session::Login::WaitingForUsername);
+    client.secure = true;
+    client.data_protection = data_protection;
+
+    Ok(Action::StartTls(protocol::reply::auth::success()))
+}
+
+/// Handle the 'PBSZ' command.
+pub fn handle_pbsz(_pbsz: &protocol::PBSZ,
+                   client: &mut ClientState)
+    -> Result<Action, Error> {
+    if !client.secure {
+        return Err(protocol::Error::from_kind(protocol::ErrorKind::InvalidCommandSequence(
+            "PBSZ must come after AUTH".to_owned(),
+        )).into());
+    }
+
+    client.protection_buffer_size_set = true;
+    Ok(Action::Reply(protocol::reply::pbsz::success()))
+}
+
+/// Handle the 'PROT' command.
+pub fn handle_prot(prot: &protocol::PROT,
+                   client: &mut ClientState)
+    -> Result<Action, Error> {
+    if !client.protection_buffer_size_set {
+        return Err(protocol::Error::from_kind(protocol::ErrorKind::InvalidCommandSequence(
+            "PROT must come after PBSZ".to_owned(),
+        )).into());
+    }
+
+    match prot.protection_level {
+        ProtectionLevel::Clear | ProtectionLevel::Private => {
+            client.data_protection = prot.protection_level;
+            Ok(Action::Reply(protocol::reply::prot::success()))
+        },
+        ProtectionLevel::Safe | ProtectionLevel::Confidential => {
+            Ok(Action::Reply(protocol::reply::prot::unsupported_level()))
+        },
+    }
+}
+
+/// Handle the 'CCC' command.
+pub fn handle_ccc(client: &mut ClientState) -> Result<Action, Error> {
+    if !client.secure {
+        return Err(protocol::Error::from_kind(protocol::ErrorKind::InvalidCommandSequence(
+            "the control connection is not protected".to_owned(),
+        )).into());
+    }
+
+    Ok(Action::Reply(protocol::reply::ccc::refused()))
+}
+
+#[cfg(test)]
+mod test
+{
+    use fs::FileSystem;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use std::path::Path;
+
+    fn start(requires_tls: bool) -> ::server::test::RunningServer {
+        let mut server = TestServer::new().with_tls();
+        server.requires_tls = requires_tls;
+        server.file_system.write_file(Path::new("/hello.txt"), b"hello".to_vec()).unwrap();
+        server.start(ListenerMode::Plain)
+    }
+
+    #[test]
+    fn protects_connections() {
+        let server = start(false);
+        let mut control = server.connect();
+        control.start_tls();
+        control.login();
+
+        assert_eq!(control.command("PBSZ 0").code.0, 200);
+        assert_eq!(control.command("PROT P").code.0, 200);
+
+        let mut data = control.passive().into_tls();
+        control.start_transfer("RETR hello.txt");
+        assert_eq!(data.read_all(), b"hello");
+        assert_eq!(control.reply().code.0, 226);
+    }
+
+    #[test]
+    fn has_users_log_in_again_once_protected() {
+        let server = start(false);
+        let mut control = server.connect();
+        control.login();
+        control.start_tls();
+
+        assert_eq!(control.command("PWD").code.0, 530);
+        control.login();
+        assert_eq!(control.command("PWD").code.0, 257);
+    }
+
+    #[test]
+    fn enforces_protection_when_required() {
+        let server = start(true);
+        let mut control = server.connect();
+        assert_eq!(control.command("USER bob").code.0, 530);
+
+        control.start_tls();
+        control.login();
+        assert_eq!(control.command("PBSZ 0").code.0, 200);
+        assert_eq!(control.command("PROT C").code.0, 200);
+        assert_eq!(control.command("RETR hello.txt").code.0, 521);
+
+        assert_eq!(control.command("PROT P").code.0, 200);
+        let mut data = control.passive().into_tls();
+        control.start_transfer("RETR hello.txt");
+        assert_eq!(data.read_all(), b"hello");
+        assert_eq!(control.reply().code.0, 226);
+    }
+}
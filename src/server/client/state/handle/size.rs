@@ -0,0 +1,68 @@
+use {Error, protocol};
+use server::Server;
+use server::client::{ClientState, Action};
+
+/// Handle the 'SIZE' command.
+pub fn handle(size: &protocol::SIZE,
+              client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+
+    let path = session.resolve_path(&size.remote_filename);
+    let metadata = server.file_system().metadata(&path)?;
+
+    if !metadata.is_file() {
+        return Ok(Action::Reply(protocol::reply::size::not_a_file()));
+    }
+
+    // RFC 3659 says that the size must be the number of bytes that
+    // would be sent. 'RETR' sends files as they are on disk, even in
+    // ASCII mode, so that is just the size of the file. Counting the
+    // line endings ASCII mode would add would make clients that check
+    // the size after downloading think the file was cut short.
+    Ok(Action::Reply(protocol::reply::size::success(metadata.size)))
+}
+
+#[cfg(test)]
+mod test
+{
+    use fs::FileSystem;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use std::path::Path;
+
+    fn start() -> ::server::test::RunningServer {
+        let mut server = TestServer::new();
+        server.file_system.create_dir(Path::new("/docs")).unwrap();
+        server.file_system.write_file(Path::new("/docs/lines.txt"), b"one\ntwo\n".to_vec()).unwrap();
+        server.start(ListenerMode::Plain)
+    }
+
+    #[test]
+    fn gives_the_size_of_files_as_they_are_sent() {
+        let server = start();
+        let mut control = server.connect();
+        control.login();
+
+        assert_eq!(control.command("TYPE I").code.0, 200);
+        assert_eq!(control.command("SIZE docs/lines.txt").text.to_string(), "8");
+        assert_eq!(control.command("TYPE A").code.0, 200);
+        assert_eq!(control.command("SIZE docs/lines.txt").text.to_string(), "8");
+
+        let mut data = control.passive();
+        control.start_transfer("RETR docs/lines.txt");
+        assert_eq!(data.read_all(), b"one\ntwo\n");
+        assert_eq!(control.reply().code.0, 226);
+    }
+
+    #[test]
+    fn refuses_missing_files_and_directories() {
+        let server = start();
+        let mut control = server.connect();
+        control.login();
+
+        assert_eq!(control.command("SIZE docs/missing.txt").code.0, 550);
+        assert_eq!(control.command("SIZE docs").code.0, 550);
+    }
+}
@@ -0,0 +1,38 @@
+use {Error, ErrorKind, server};
+use server::Server;
+use server::client::{ClientState, Action};
+
+use std::io;
+
+use uuid::Uuid;
+
+/// Handle the 'STOU' command.
+pub fn handle(client: &mut ClientState,
+              server: &mut Server)
+    -> Result<Action, Error> {
+    let session = client.session.expect_ready()?;
+    let mode = server::StoreMode::Unique;
+
+    // UUIDs will practically never collide, but the file is only
+    // created if it doesn't exist so that we can never clobber one.
+    // It is created straight away to reserve the name we tell the client.
+    let (path, file) = loop {
+        let path = session.resolve_path(&Uuid::new_v4().simple().to_string());
+
+        match mode.open(&path, server.file_system_mut()) {
+            Ok(file) => break (path, file),
+            Err(Error(ErrorKind::Io(ref e), _)) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    };
+    debug!("client is uploading to unique file {}", path.display());
+
+    Ok(Action::Transfer(server::Transfer {
+        file_type: session.transfer_type,
+        direction: server::Direction::Incoming {
+            path: path,
+            mode: mode,
+            file: Some(file),
+        },
+    }))
+}
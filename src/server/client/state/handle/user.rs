@@ -12,6 +12,11 @@ pub fn handle(user: &protocol::USER,
     let session = client.session.expect_login()?.clone();
 
     if let session::Login::WaitingForUsername = session {
+        if server.requires_tls(&user.username) && !client.secure {
+            return Ok(Action::Reply(protocol::reply::user::not_logged_in(
+                "TLS is required, send AUTH TLS first")));
+        }
+
         let credentials = Credentials { username: user.username.to_owned(), password: None };
 
         // The user may authenticate with no password
@@ -9,6 +9,7 @@ use server::Server;
 use io::Connection;
 
 use std;
+use std::net::IpAddr;
 
 use uuid::Uuid;
 
@@ -20,15 +21,30 @@ pub struct ClientState
 {
     pub uuid: Uuid,
     pub session: Session,
+    /// The address the client connected to us from.
+    pub peer_ip: IpAddr,
+    /// Whether the control connection is protected with TLS.
+    pub secure: bool,
+    /// Whether the client has sent 'PBSZ', which must come before 'PROT'.
+    pub protection_buffer_size_set: bool,
+    /// How data connections are protected.
+    pub data_protection: protocol::ProtectionLevel,
+    /// The facts to give in 'MLST' and 'MLSD' listings.
+    pub mlst_facts: Vec<protocol::facts::FactKind>,
 }
 
 impl ClientState
 {
     /// Creates a new client state.
-    pub fn new() -> Self {
+    pub fn new(peer_ip: IpAddr) -> Self {
         ClientState {
             uuid: Uuid::new_v4(),
             session: Default::default(),
+            peer_ip: peer_ip,
+            secure: false,
+            protection_buffer_size_set: false,
+            data_protection: protocol::ProtectionLevel::default(),
+            mlst_facts: protocol::facts::FactKind::all().to_owned(),
         }
     }
 
@@ -51,8 +67,8 @@ impl ClientState
             Session::PendingWelcome => {
                 debug!("sending welcome to client");
 
-                let welcome = protocol::Reply::new(protocol::reply::code::OK, server.welcome_message());
-                welcome.write(&mut connection.pi.stream)?;
+                let welcome = protocol::Reply::new(protocol::reply::code::SERVICE_READY_FOR_NEW_USER, server.welcome_message());
+                connection.send_reply(welcome)?;
 
                 Session::Login(session::Login::WaitingForUsername)
             },
@@ -3,10 +3,10 @@ use io::DataTransferMode;
 use {server, protocol};
 
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 /// The state of a client.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum Session
 {
     /// We need to send them a welcome message.
@@ -30,7 +30,7 @@ pub enum Login
 }
 
 /// The state of a client that is connected and ready for work.
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Ready
 {
     /// The credentials of the current user.
@@ -47,6 +47,12 @@ pub struct Ready
     pub client_addr: Option<SocketAddr>,
     /// The data transfer operations we have queued.
     pub active_transfer: Option<server::Transfer>,
+    /// The file named by 'RNFR', waiting for an 'RNTO'.
+    pub rename_from: Option<PathBuf>,
+    /// The offset given by 'REST', waiting for a transfer to resume.
+    pub restart_marker: Option<u64>,
+    /// Whether the client has sent 'EPSV ALL'.
+    pub epsv_all: bool,
 }
 
 impl Session
@@ -88,8 +94,30 @@ impl Ready
             data_transfer_mode: DataTransferMode::default(),
             client_addr: None,
             active_transfer: None,
+            rename_from: None,
+            restart_marker: None,
+            epsv_all: false,
         }
     }
+
+    /// Resolves a path sent by the client into an absolute path.
+    ///
+    /// Relative paths are relative to the working directory. Any
+    /// '.' or '..' components are normalized away.
+    pub fn resolve_path<P>(&self, path: P) -> PathBuf
+        where P: AsRef<Path> {
+        let mut resolved = PathBuf::from("/");
+
+        for component in self.working_dir.join(path).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => { resolved.pop(); },
+                Component::RootDir | Component::CurDir | Component::Prefix(..) => (),
+            }
+        }
+
+        resolved
+    }
 }
 
 impl Default for Session
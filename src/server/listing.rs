@@ -0,0 +1,208 @@
+//! Formatting of directory listings.
+
+use fs::{DirEntry, FileKind};
+use protocol::facts::{self, EntryType, Facts};
+use protocol::time::DateTime;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Files modified longer ago than this show their year instead
+/// of their time, just like `ls` does.
+const RECENT_DURATION: Duration = Duration::from_secs(60 * 60 * 24 * 365 / 2);
+
+/// Formats an entry as a line of `ls -l` output, without a line ending.
+///
+/// For example `-rw-r--r--   1 ftp      ftp          1024 Jul 14 02:40 foo.txt`.
+pub fn long_format(entry: &DirEntry, now: SystemTime) -> String {
+    let metadata = &entry.metadata;
+    let modified = DateTime::from_system_time(metadata.modified);
+
+    let is_recent = match now.duration_since(metadata.modified) {
+        Ok(age) => age < RECENT_DURATION,
+        // Files from the future are treated as old, like `ls` does.
+        Err(..) => false,
+    };
+    let time_or_year = if is_recent {
+        format!("{:02}:{:02}", modified.hour, modified.minute)
+    } else {
+        format!(" {}", modified.year)
+    };
+
+    let mut line = format!("{}{} {:>3} {:<8} {:<8} {:>8} {} {:>2} {:>5} {}",
+                           kind_character(metadata.kind),
+                           permission_string(metadata.permissions),
+                           1,
+                           metadata.owner,
+                           metadata.group,
+                           metadata.size,
+                           modified.month_abbreviation(),
+                           modified.day,
+                           time_or_year,
+                           entry.name);
+
+    if let Some(ref target) = metadata.symlink_target {
+        line.push_str(&format!(" -> {}", target.display()));
+    }
+
+    line
+}
+
+/// Gathers the 'MLST' facts about an entry.
+///
+/// * `path` is the full path of the entry.
+pub fn facts_entry(entry: &DirEntry, path: &Path) -> facts::Entry {
+    let metadata = &entry.metadata;
+
+    let entry_type = match metadata.kind {
+        FileKind::File => EntryType::File,
+        FileKind::Directory => EntryType::Dir,
+        FileKind::Symlink => match metadata.symlink_target {
+            Some(ref target) => EntryType::Other(format!("OS.unix=slink:{}", target.display())),
+            None => EntryType::Other("OS.unix=symlink".to_owned()),
+        },
+    };
+
+    // We don't have inode numbers for every file system, so
+    // the path is the best identifier we have.
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+
+    facts::Entry {
+        facts: Facts {
+            entry_type: Some(entry_type),
+            size: if metadata.is_dir() { None } else { Some(metadata.size) },
+            modify: Some(metadata.modified),
+            perm: Some(perm_fact(metadata.kind, metadata.permissions)),
+            unique: Some(format!("{:x}", hasher.finish())),
+            unix_mode: Some(metadata.permissions),
+            unix_owner: Some(metadata.owner.clone()),
+            unix_group: Some(metadata.group.clone()),
+        },
+        name: entry.name.clone(),
+    }
+}
+
+/// Works out the 'perm' fact from the owner's permission bits.
+fn perm_fact(kind: FileKind, permissions: u32) -> String {
+    let (readable, writable) = (permissions & 0o400 != 0, permissions & 0o200 != 0);
+
+    let (read_perms, write_perms) = match kind {
+        // Enter, list / create, delete, rename, make directory, purge.
+        FileKind::Directory => ("el", "cdfmp"),
+        // Read / append, delete, rename, write.
+        FileKind::File | FileKind::Symlink => ("r", "adfw"),
+    };
+
+    let mut perm = String::new();
+    if readable { perm.push_str(read_perms); }
+    if writable { perm.push_str(write_perms); }
+    perm
+}
+
+/// Gets the character `ls` uses to show the kind of a file.
+fn kind_character(kind: FileKind) -> char {
+    match kind {
+        FileKind::File => '-',
+        FileKind::Directory => 'd',
+        FileKind::Symlink => 'l',
+    }
+}
+
+/// Formats permission bits like `rwxr-xr-x`.
+fn permission_string(permissions: u32) -> String {
+    let mut s = String::new();
+
+    for &(shift, special) in [(6, 0o4000), (3, 0o2000), (0, 0o1000)].iter() {
+        let bits = permissions >> shift;
+        let is_special = permissions & special != 0;
+
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 0o1 != 0, is_special) {
+            (true, false) => 'x',
+            (false, false) => '-',
+            // The setuid, setgid and sticky bits.
+            (true, true) => if shift == 0 { 't' } else { 's' },
+            (false, true) => if shift == 0 { 'T' } else { 'S' },
+        });
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod test
+{
+    pub use super::*;
+    use fs::Metadata;
+    use std::time::UNIX_EPOCH;
+
+    fn entry(kind: FileKind, permissions: u32, modified: u64) -> DirEntry {
+        DirEntry {
+            name: "foo.txt".to_owned(),
+            metadata: Metadata {
+                kind: kind,
+                size: 1024,
+                modified: UNIX_EPOCH + Duration::from_secs(modified),
+                permissions: permissions,
+                owner: "ftp".to_owned(),
+                group: "ftp".to_owned(),
+                symlink_target: None,
+            },
+        }
+    }
+
+    #[test]
+    fn correctly_formats_a_recent_file() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_500_000_000 + 60);
+
+        assert_eq!(long_format(&entry(FileKind::File, 0o644, 1_500_000_000), now),
+                   "-rw-r--r--   1 ftp      ftp          1024 Jul 14 02:40 foo.txt");
+    }
+
+    #[test]
+    fn correctly_formats_an_old_directory() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        assert_eq!(long_format(&entry(FileKind::Directory, 0o755, 1_500_000_000), now),
+                   "drwxr-xr-x   1 ftp      ftp          1024 Jul 14  2017 foo.txt");
+    }
+
+    #[test]
+    fn correctly_formats_a_symlink() {
+        let mut entry = entry(FileKind::Symlink, 0o777, 0);
+        entry.metadata.symlink_target = Some("/bar.txt".into());
+
+        assert!(long_format(&entry, UNIX_EPOCH).ends_with(" foo.txt -> /bar.txt"));
+        assert!(long_format(&entry, UNIX_EPOCH).starts_with("lrwxrwxrwx "));
+    }
+
+    #[test]
+    fn correctly_gathers_facts() {
+        let facts = facts_entry(&entry(FileKind::File, 0o644, 0), Path::new("/foo.txt")).facts;
+
+        assert_eq!(facts.entry_type, Some(EntryType::File));
+        assert_eq!(facts.size, Some(1024));
+        assert_eq!(facts.perm, Some("radfw".to_owned()));
+        assert_eq!(facts.unix_mode, Some(0o644));
+    }
+
+    #[test]
+    fn correctly_gathers_facts_about_read_only_directories() {
+        let facts = facts_entry(&entry(FileKind::Directory, 0o555, 0), Path::new("/foo")).facts;
+
+        assert_eq!(facts.entry_type, Some(EntryType::Dir));
+        assert_eq!(facts.size, None);
+        assert_eq!(facts.perm, Some("el".to_owned()));
+    }
+
+    #[test]
+    fn correctly_formats_special_permission_bits() {
+        assert_eq!(permission_string(0o4755), "rwsr-xr-x");
+        assert_eq!(permission_string(0o1777), "rwxrwxrwt");
+        assert_eq!(permission_string(0o2640), "rw-r-S---");
+    }
+}
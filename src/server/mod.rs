@@ -1,13 +1,19 @@
 //! Utilities for setting up FTP servers.
 
 pub use self::server::Server;
-pub use self::run::run;
+pub use self::run::{run, run_with_mode, run_with_listener, ListenerMode};
+pub use self::policy::DataAddressPolicy;
 
-use self::transfer::Transfer;
+use self::transfer::{Transfer, Direction, StoreMode};
 
 mod server;
 mod transfer;
+mod listing;
+mod policy;
 mod run;
 
 mod client;
 
+#[cfg(test)]
+pub mod test;
+
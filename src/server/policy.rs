@@ -0,0 +1,87 @@
+//! Rules for the data connections a client may ask for.
+
+use std::net::{IpAddr, SocketAddr};
+
+/// Ports below this can only be bound by privileged users.
+const FIRST_UNPRIVILEGED_PORT: u16 = 1024;
+
+/// Which addresses a client may ask us to open active data connections to.
+///
+/// Without any rules, 'PORT' and 'EPRT' let clients use the server to
+/// connect to third parties. This is the FTP bounce attack from RFC 2577.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DataAddressPolicy
+{
+    /// Whether data connections may go to hosts other than the client.
+    ///
+    /// Server-to-server transfers (FXP) need this.
+    pub allow_other_hosts: bool,
+    /// Whether data connections may go to ports below 1024.
+    pub allow_privileged_ports: bool,
+}
+
+impl DataAddressPolicy
+{
+    /// Only allows data connections to unprivileged ports on the client.
+    pub fn strict() -> Self {
+        DataAddressPolicy { allow_other_hosts: false, allow_privileged_ports: false }
+    }
+
+    /// Allows data connections to any address, as FXP needs.
+    pub fn fxp() -> Self {
+        DataAddressPolicy { allow_other_hosts: true, allow_privileged_ports: true }
+    }
+
+    /// Checks an address a client connected from `peer_ip` asked us to connect to.
+    ///
+    /// Gives the reason if the address is not allowed.
+    pub fn check(&self, addr: &SocketAddr, peer_ip: IpAddr) -> Result<(), &'static str> {
+        if !self.allow_other_hosts && addr.ip() != peer_ip {
+            Err("data connections to other hosts are not allowed")
+        } else if !self.allow_privileged_ports && addr.port() < FIRST_UNPRIVILEGED_PORT {
+            Err("data connections to privileged ports are not allowed")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for DataAddressPolicy
+{
+    fn default() -> Self { DataAddressPolicy::strict() }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn peer() -> IpAddr { "192.168.1.5".parse().unwrap() }
+
+    #[test]
+    fn strict_allows_unprivileged_ports_on_the_client() {
+        let policy = DataAddressPolicy::strict();
+        assert_eq!(policy.check(&"192.168.1.5:50000".parse().unwrap(), peer()), Ok(()));
+        assert_eq!(policy.check(&"192.168.1.5:1024".parse().unwrap(), peer()), Ok(()));
+    }
+
+    #[test]
+    fn strict_rejects_other_hosts() {
+        let policy = DataAddressPolicy::strict();
+        assert!(policy.check(&"10.0.0.1:50000".parse().unwrap(), peer()).is_err());
+        assert!(policy.check(&"[::1]:50000".parse().unwrap(), peer()).is_err());
+    }
+
+    #[test]
+    fn strict_rejects_privileged_ports() {
+        let policy = DataAddressPolicy::strict();
+        assert!(policy.check(&"192.168.1.5:25".parse().unwrap(), peer()).is_err());
+        assert!(policy.check(&"192.168.1.5:1023".parse().unwrap(), peer()).is_err());
+    }
+
+    #[test]
+    fn fxp_allows_anything() {
+        let policy = DataAddressPolicy::fxp();
+        assert_eq!(policy.check(&"10.0.0.1:21".parse().unwrap(), peer()), Ok(()));
+    }
+}
@@ -3,19 +3,18 @@
 use Error;
 use server::Server;
 use server::client::{Client, ClientState};
-use io::{Connection, Io, Interpreter, DataTransfer};
+use io::{Connection, Io, Interpreter, DataTransfer, Stream};
+use protocol::ProtectionLevel;
 
 use uuid::Uuid;
 use mio::unix::UnixReady;
 use mio::tcp::TcpListener;
 use mio::*;
+use net2::TcpBuilder;
 
 use std::collections::{HashMap, hash_map};
 use std::time::Duration;
-use std::net::ToSocketAddrs;
-
-/// The mio token used for the server connection.
-const SERVER_TOKEN: Token = Token(0);
+use std::net::{self, SocketAddr, ToSocketAddrs};
 
 /// The state of an FTP server.
 struct ServerState
@@ -23,36 +22,101 @@ struct ServerState
     pub clients: HashMap<Uuid, Client>,
 }
 
+/// How clients connecting to the server protect their connections.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ListenerMode
+{
+    /// Connections start in plain text. Clients may protect them
+    /// with 'AUTH TLS' if the server has a TLS configuration.
+    Plain,
+    /// Control and data connections are protected with TLS from
+    /// the first byte, usually on port 990.
+    ImplicitTls,
+}
+
 /// Runs a FTP server on a given address.
 ///
 /// Sets up an FTP server locally and begins to wait for clients
 /// to connect.
+///
+/// We listen on every address the given address resolves to, so
+/// `localhost` accepts both IPv4 and IPv6 clients. Returns once
+/// `Server::is_stopped` is true.
 pub fn run<F,A>(server: &mut F, address: A) -> Result<(), Error>
     where F: Server,
           A: ToSocketAddrs {
-    let mut addresses = address.to_socket_addrs()?;
-    let address = match addresses.next() {
-        Some(addr) => addr,
-        None => return Err("could not resolve to any addresses".into()),
+    run_with_mode(server, address, ListenerMode::Plain)
+}
+
+/// Runs a FTP server on a given address, in the given listener mode.
+pub fn run_with_mode<F,A>(server: &mut F, address: A, mode: ListenerMode) -> Result<(), Error>
+    where F: Server,
+          A: ToSocketAddrs {
+    let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    if addresses.is_empty() {
+        return Err("could not resolve to any addresses".into());
+    }
+
+    let mut listeners = Vec::new();
+    for address in addresses.iter() {
+        listeners.push(listen(address, addresses.len() > 1)?);
+    }
+
+    serve(server, listeners, mode)
+}
+
+/// Runs a FTP server on a listener that has already been bound.
+///
+/// Binding to port 0 and asking the listener for its address gets
+/// a free port without racing anything else for it.
+pub fn run_with_listener<F>(server: &mut F, listener: net::TcpListener, mode: ListenerMode)
+    -> Result<(), Error>
+    where F: Server {
+    serve(server, vec![TcpListener::from_std(listener)?], mode)
+}
+
+/// Accepts clients on a set of listeners and serves them until
+/// the server is stopped.
+fn serve<F>(server: &mut F, listeners: Vec<TcpListener>, mode: ListenerMode) -> Result<(), Error>
+    where F: Server {
+    let implicit_tls_config = match mode {
+        ListenerMode::Plain => None,
+        ListenerMode::ImplicitTls => match server.tls_config() {
+            Some(config) => Some(config),
+            None => return Err("implicit TLS needs the server to have a TLS configuration".into()),
+        },
     };
 
     debug!("running server");
 
-    // Setup the server socket
-    let listener = TcpListener::bind(&address)?;
     let mut io = Io::new()?;
 
-    // Start listening for incoming connections
-    io.poll.register(&listener, SERVER_TOKEN, Ready::readable(),
-                     PollOpt::edge())?;
+    // Start listening for incoming connections. The listener
+    // tokens are the indices into the list of listeners.
+    for (index, listener) in listeners.iter().enumerate() {
+        io.poll.register(listener, Token(index), Ready::readable(),
+                         PollOpt::edge())?;
+    }
 
     // Create storage for events
     let mut events = Events::with_capacity(1024);
     let mut state = ServerState::new();
 
-    loop {
+    while !server.is_stopped() {
+        // A client that fails is disconnected, like it would be if
+        // it failed while handling an event.
+        let mut failed_clients = Vec::new();
         for client_data in state.clients.values_mut() {
-            client_data.tick(&mut io)?;
+            if let Err(e) = client_data.tick(server, &mut io) {
+                info!("error while ticking client ({}): {:?}", client_data.state.uuid, e);
+                failed_clients.push(client_data.state.uuid);
+            }
+        }
+
+        for client_uuid in failed_clients {
+            if let Some(client) = state.clients.remove(&client_uuid) {
+                client.close(server);
+            }
         }
 
         io.poll.poll(&mut events, Some(Duration::from_millis(30)))?;
@@ -61,26 +125,43 @@ pub fn run<F,A>(server: &mut F, address: A) -> Result<(), Error>
             let readiness = UnixReady::from(event.readiness());
 
             match event.token() {
-                SERVER_TOKEN => {
+                Token(index) if index < listeners.len() => {
                     // Accept and drop the socket immediately, this will close
                     // the socket and notify the client of the EOF.
-                    let (sock, _) = listener.accept()?;
+                    let (sock, _) = listeners[index].accept()?;
 
                     // Increase the token accumulator so the connection gets a unique token.
                     let token = io.allocate_token();
                     io.poll.register(&sock, token, Ready::readable() | UnixReady::hup(),
                                   PollOpt::edge())?;
 
-                    let mut client_state = ClientState::new();
-
                     let mut connection = Connection {
-                        pi: Interpreter {
-                            stream: sock,
-                            token: token,
-                        },
+                        pi: Interpreter::new(Stream::new(sock), token),
                         dtp: DataTransfer::None,
                     };
 
+                    // The client may already have hung up.
+                    let peer_ip = match connection.pi.peer_ip() {
+                        Ok(peer_ip) => peer_ip,
+                        Err(e) => {
+                            info!("could not get the address of a new client: {}", e);
+                            continue 'events;
+                        },
+                    };
+
+                    let mut client_state = ClientState::new(peer_ip);
+
+                    if let Some(ref config) = implicit_tls_config {
+                        // The welcome message is sent once the handshake is done.
+                        connection.pi.stream.start_tls(config);
+
+                        // Data connections are protected as well, without
+                        // the client needing to send 'PBSZ' and 'PROT'.
+                        client_state.secure = true;
+                        client_state.protection_buffer_size_set = true;
+                        client_state.data_protection = ProtectionLevel::Private;
+                    }
+
                     match client_state.progress(server, &mut connection) {
                         Ok(..) => {
                             debug!("a client has connected ({})", client_state.uuid);
@@ -97,7 +178,11 @@ pub fn run<F,A>(server: &mut F, address: A) -> Result<(), Error>
 
                 },
                 token => {
-                    let client_uuid = state.clients.values().find(|client| client.connection.uses_token(token)).unwrap().state.uuid;
+                    // The event may be for a data connection we have since closed.
+                    let client_uuid = match state.clients.values().find(|client| client.connection.uses_token(token)) {
+                        Some(client) => client.state.uuid,
+                        None => continue 'events,
+                    };
                     let mut client = if let hash_map::Entry::Occupied(entry) = state.clients.entry(client_uuid) { entry } else { unreachable!() };
 
                     let mut should_remove = false;
@@ -111,18 +196,49 @@ pub fn run<F,A>(server: &mut F, address: A) -> Result<(), Error>
                     }
 
                     if should_remove {
-                        client.remove();
+                        client.remove().close(server);
                         continue 'events;
                     }
 
-                    if readiness.is_hup() {
+                    // The client closing a data connection is a normal part
+                    // of a transfer, only a hangup on the PI stream counts.
+                    if readiness.is_hup() && client.get().connection.pi.token == token {
                         info!("client disconnected");
-                        client.remove();
+                        client.remove().close(server);
                     }
                 }
             }
         }
     }
+
+    debug!("stopping server");
+
+    // Dropping the connections disconnects the clients.
+    for (_, client) in state.clients.drain() {
+        client.close(server);
+    }
+
+    Ok(())
+}
+
+/// Opens a listener for control connections.
+///
+/// IPv6 listeners also accept IPv4 clients, unless `only_v6` is set
+/// because we are listening on an IPv4 address as well.
+fn listen(address: &SocketAddr, only_v6: bool) -> Result<TcpListener, Error> {
+    let builder = match *address {
+        SocketAddr::V4(..) => TcpBuilder::new_v4()?,
+        SocketAddr::V6(..) => {
+            let builder = TcpBuilder::new_v6()?;
+            builder.only_v6(only_v6)?;
+            builder
+        },
+    };
+
+    builder.reuse_address(true)?;
+    builder.bind(address)?;
+
+    Ok(TcpListener::from_std(builder.listen(1024)?)?)
 }
 
 impl ServerState
@@ -133,3 +249,29 @@ impl ServerState
     }
 }
 
+
+#[cfg(test)]
+mod test
+{
+    use fs::FileSystem;
+    use server::ListenerMode;
+    use server::test::TestServer;
+    use std::path::Path;
+
+    #[test]
+    fn protects_everything_with_implicit_tls() {
+        let mut server = TestServer::new().with_tls();
+        server.file_system.write_file(Path::new("/hello.txt"), b"hello".to_vec()).unwrap();
+        let server = server.start(ListenerMode::ImplicitTls);
+
+        // The greeting only comes once the handshake is done.
+        let mut control = server.connect_tls();
+        control.login();
+
+        // Data connections are protected without 'PBSZ' and 'PROT'.
+        let mut data = control.passive().into_tls();
+        control.start_transfer("RETR hello.txt");
+        assert_eq!(data.read_all(), b"hello");
+        assert_eq!(control.reply().code.0, 226);
+    }
+}
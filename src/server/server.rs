@@ -2,6 +2,14 @@
 
 use Credentials;
 use fs::FileSystem;
+use server::DataAddressPolicy;
+
+use rustls;
+
+use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// An FTP server instance.
 pub trait Server
@@ -14,5 +22,63 @@ pub trait Server
 
     fn file_system(&self) -> &FileSystem;
     fn file_system_mut(&mut self) -> &mut FileSystem;
-}
 
+    /// Gets the ports we may listen on for passive data connections.
+    ///
+    /// Defaults to the dynamic port range.
+    fn passive_ports(&self) -> RangeInclusive<u16> { 49152..=65535 }
+
+    /// Gets the address clients are told to open passive data connections to.
+    ///
+    /// By default this is the address the client connected to. Servers
+    /// behind NAT, or running in a container, should give the public
+    /// address they are reachable at.
+    fn passive_address(&self) -> Option<Ipv4Addr> { None }
+
+    /// Whether passive data connections must come from the same
+    /// host as the control connection.
+    ///
+    /// Anybody can connect to the port we listen on, so without this
+    /// check another host could steal or replace the data being
+    /// transferred. Servers supporting FXP need to turn it off.
+    fn verify_passive_peer(&self) -> bool { true }
+
+    /// Gets how long we wait for the client to open a passive data
+    /// connection once it has requested a transfer.
+    fn passive_timeout(&self) -> Duration { Duration::from_secs(60) }
+
+    /// Whether active data connections should come from port L-1,
+    /// where L is the port clients connect to.
+    ///
+    /// This is port 20 when serving on the standard port 21, as
+    /// RFC 959 asks for. Ports below 1024 usually need root, so
+    /// by default the operating system picks the port.
+    fn active_from_data_port(&self) -> bool { false }
+
+    /// Gets the addresses a user may ask us to open active data connections to.
+    ///
+    /// By default users may only be connected back to unprivileged
+    /// ports on their own host. Servers that support FXP can allow
+    /// other addresses for the users that need them.
+    fn data_address_policy(&self, _credentials: &Credentials) -> DataAddressPolicy {
+        DataAddressPolicy::default()
+    }
+
+    /// Gets the TLS configuration for protecting connections.
+    ///
+    /// Clients can only use 'AUTH TLS' if this is given. The
+    /// `io::tls::server_config` function loads one from PEM files.
+    fn tls_config(&self) -> Option<Arc<rustls::ServerConfig>> { None }
+
+    /// Whether a user must protect their connections with TLS.
+    ///
+    /// The user must send 'AUTH TLS' before logging in, and
+    /// 'PROT P' before transferring any data.
+    fn requires_tls(&self, _username: &str) -> bool { false }
+
+    /// Whether the server should stop.
+    ///
+    /// This is checked at least every tick, and once it is true the
+    /// clients are disconnected and `run` returns.
+    fn is_stopped(&self) -> bool { false }
+}
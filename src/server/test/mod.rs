@@ -0,0 +1,310 @@
+//! Helpers for testing servers over real connections.
+
+use fs::{self, FileSystem};
+use io::tls;
+use server::{self, ListenerMode};
+use protocol::Reply;
+
+use rustls;
+use webpki;
+
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{self, Ipv4Addr, SocketAddr, TcpStream};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::{io, thread};
+
+/// How long a test waits for the server before giving up.
+const TIMEOUT_SECS: u64 = 5;
+
+/// A server that tests can configure.
+pub struct TestServer
+{
+    pub file_system: fs::Memory,
+    pub passive_ports: RangeInclusive<u16>,
+    pub passive_address: Option<Ipv4Addr>,
+    pub verify_passive_peer: bool,
+    pub passive_timeout: Duration,
+    pub tls_config: Option<Arc<rustls::ServerConfig>>,
+    pub requires_tls: bool,
+    stopped: Arc<AtomicBool>,
+}
+
+impl TestServer
+{
+    pub fn new() -> Self {
+        TestServer {
+            file_system: fs::Memory::new(),
+            passive_ports: 49152..=65535,
+            passive_address: None,
+            verify_passive_peer: true,
+            passive_timeout: Duration::from_secs(60),
+            tls_config: None,
+            requires_tls: false,
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Protects connections with the self-signed test certificate.
+    pub fn with_tls(mut self) -> Self {
+        let config = tls::server_config(&test_file("cert.pem"), &test_file("key.pem")).unwrap();
+
+        self.tls_config = Some(config);
+        self
+    }
+
+    /// Starts serving on a free local port in the background.
+    pub fn start(self, mode: ListenerMode) -> RunningServer {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let stopped = self.stopped.clone();
+
+        let mut server = self;
+        let thread = thread::spawn(move || {
+            server::run_with_listener(&mut server, listener, mode).unwrap();
+            server
+        });
+
+        RunningServer { address: address, stopped: stopped, thread: Some(thread) }
+    }
+}
+
+impl server::Server for TestServer
+{
+    fn welcome_message(&self) -> String { "test server".to_owned() }
+
+    fn file_system(&self) -> &FileSystem { &self.file_system }
+    fn file_system_mut(&mut self) -> &mut FileSystem { &mut self.file_system }
+
+    fn passive_ports(&self) -> RangeInclusive<u16> { self.passive_ports.clone() }
+    fn passive_address(&self) -> Option<Ipv4Addr> { self.passive_address }
+    fn verify_passive_peer(&self) -> bool { self.verify_passive_peer }
+    fn passive_timeout(&self) -> Duration { self.passive_timeout }
+
+    fn tls_config(&self) -> Option<Arc<rustls::ServerConfig>> { self.tls_config.clone() }
+    fn requires_tls(&self, _username: &str) -> bool { self.requires_tls }
+
+    fn is_stopped(&self) -> bool { self.stopped.load(Ordering::SeqCst) }
+}
+
+/// A server running in the background.
+///
+/// The server is stopped when this is dropped.
+pub struct RunningServer
+{
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<TestServer>>,
+}
+
+impl RunningServer
+{
+    /// Gets the address of the control connection.
+    pub fn address(&self) -> SocketAddr { self.address }
+
+    /// Opens a plain text control connection and reads the greeting.
+    pub fn connect(&self) -> Control {
+        let mut control = Control::new(Stream::Plain(connect(self.address)));
+        assert_eq!(control.reply().code.0, 220);
+        control
+    }
+
+    /// Opens a control connection with implicit TLS and reads the greeting.
+    pub fn connect_tls(&self) -> Control {
+        let mut control = Control::new(Stream::tls(connect(self.address)));
+        assert_eq!(control.reply().code.0, 220);
+        control
+    }
+
+    /// Stops the server, giving back its final state.
+    pub fn stop(mut self) -> TestServer {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.thread.take().unwrap().join().unwrap()
+    }
+}
+
+impl Drop for RunningServer
+{
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// A control connection to a test server.
+pub struct Control
+{
+    stream: BufReader<Stream>,
+}
+
+impl Control
+{
+    fn new(stream: Stream) -> Self {
+        Control { stream: BufReader::new(stream) }
+    }
+
+    /// Sends a command without waiting for the reply.
+    pub fn send(&mut self, line: &str) {
+        let stream = self.stream.get_mut();
+        stream.write_all(format!("{}\r\n", line).as_bytes()).unwrap();
+        stream.flush().unwrap();
+    }
+
+    /// Reads the next reply.
+    pub fn reply(&mut self) -> Reply {
+        Reply::read(&mut self.stream).unwrap()
+    }
+
+    /// Reads the next reply, if one comes in time.
+    pub fn reply_within(&mut self, timeout: Duration) -> Option<Reply> {
+        self.stream.get_ref().socket().set_read_timeout(Some(timeout)).unwrap();
+        let reply = Reply::read(&mut self.stream).ok();
+        self.stream.get_ref().socket().set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECS))).unwrap();
+
+        reply
+    }
+
+    /// Sends a command and reads its reply.
+    pub fn command(&mut self, line: &str) -> Reply {
+        self.send(line);
+        self.reply()
+    }
+
+    /// Sends a command that transfers data, expecting it to start.
+    pub fn start_transfer(&mut self, line: &str) {
+        // Depending on whether we have accepted the data connection yet.
+        let code = self.command(line).code.0;
+        assert!(code == 125 || code == 150, "transfer did not start: {}", code);
+    }
+
+    /// Logs in. Test servers let anybody in without a password.
+    pub fn login(&mut self) {
+        assert_eq!(self.command("USER bob").code.0, 230);
+    }
+
+    /// Sends 'AUTH TLS' and protects the rest of the connection.
+    pub fn start_tls(&mut self) {
+        assert_eq!(self.command("AUTH TLS").code.0, 234);
+
+        let stream = match *self.stream.get_ref() {
+            Stream::Plain(ref stream) => stream.try_clone().unwrap(),
+            Stream::Tls(..) => panic!("connection is already protected"),
+        };
+        self.stream = BufReader::new(Stream::tls(stream));
+    }
+
+    /// Asks for a passive data connection, giving the address to open it to.
+    pub fn epsv(&mut self) -> SocketAddr {
+        let reply = self.command("EPSV");
+        assert_eq!(reply.code.0, 229);
+
+        let text = reply.text.to_string();
+        let port = text.split('|').nth(3).unwrap().parse().unwrap();
+        SocketAddr::new(self.stream.get_ref().socket().peer_addr().unwrap().ip(), port)
+    }
+
+    /// Asks for a passive data connection and opens it.
+    pub fn passive(&mut self) -> Stream {
+        let address = self.epsv();
+        Stream::Plain(connect(address))
+    }
+}
+
+/// A connection to a test server, which may be protected with TLS.
+pub enum Stream
+{
+    Plain(TcpStream),
+    Tls(rustls::StreamOwned<rustls::ClientSession, TcpStream>),
+}
+
+impl Stream
+{
+    /// Protects a connection with TLS, trusting only the test certificate.
+    pub fn tls(stream: TcpStream) -> Self {
+        let certificate = ::std::fs::read(test_file("cert.pem")).unwrap();
+
+        let mut config = rustls::ClientConfig::new();
+        config.root_store.add_pem_file(&mut &certificate[..]).unwrap();
+
+        let name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+        let session = rustls::ClientSession::new(&Arc::new(config), name);
+
+        Stream::Tls(rustls::StreamOwned::new(session, stream))
+    }
+
+    /// Protects a plain connection with TLS.
+    pub fn into_tls(self) -> Self {
+        match self {
+            Stream::Plain(stream) => Stream::tls(stream),
+            Stream::Tls(..) => panic!("connection is already protected"),
+        }
+    }
+
+    /// Reads everything until the server closes the connection.
+    pub fn read_all(&mut self) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        match self.read_to_end(&mut data) {
+            Ok(..) => (),
+            // This is how rustls tells us the server sent 'close_notify'.
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionAborted => (),
+            Err(e) => panic!("could not read data connection: {}", e),
+        }
+
+        data
+    }
+
+    fn socket(&self) -> &TcpStream {
+        match *self {
+            Stream::Plain(ref stream) => stream,
+            Stream::Tls(ref stream) => &stream.sock,
+        }
+    }
+}
+
+impl Read for Stream
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Plain(ref mut stream) => stream.read(buf),
+            Stream::Tls(ref mut stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Plain(ref mut stream) => stream.write(buf),
+            Stream::Tls(ref mut stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Plain(ref mut stream) => stream.flush(),
+            Stream::Tls(ref mut stream) => stream.flush(),
+        }
+    }
+}
+
+/// Connects to the server, timing out if it stops responding.
+fn connect(address: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECS))).unwrap();
+    stream.set_write_timeout(Some(Duration::from_secs(TIMEOUT_SECS))).unwrap();
+    stream
+}
+
+/// Gets the path to a file kept alongside these helpers.
+fn test_file(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/server/test").join(name)
+}
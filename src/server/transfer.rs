@@ -1,11 +1,190 @@
 //! The `Transfer` type.
 
-use FileType;
+use {Error, ErrorKind, FileType};
+use fs::{FileSystem, WriteMode};
+
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
+
+use uuid::Uuid;
 
 /// A data transfer.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct Transfer
 {
     pub file_type: FileType,
-    pub data: Vec<u8>,
+    /// Which way the data is flowing.
+    pub direction: Direction,
+}
+
+impl Transfer
+{
+    /// Gets the name we picked for the file if this is a 'STOU' upload.
+    pub fn unique_file_name(&self) -> Option<String> {
+        match self.direction {
+            Direction::Incoming { ref path, mode: StoreMode::Unique, .. } => {
+                path.file_name().map(|name| name.to_string_lossy().into_owned())
+            },
+            _ => None,
+        }
+    }
+
+    /// Throws away what we have received of an unfinished upload.
+    pub fn discard(self, file_system: &mut FileSystem) {
+        if let Direction::Incoming { mode, file: Some(file), .. } = self.direction {
+            mode.discard(file, file_system);
+        }
+    }
+}
+
+/// The direction data is flowing over the DTP connection.
+pub enum Direction
+{
+    /// We are sending data to the client.
+    Outgoing {
+        /// Where the data comes from.
+        reader: Box<Read>,
+        /// The chunk of data we are currently sending.
+        chunk: Vec<u8>,
+        /// How much of the chunk we have sent so far.
+        position: usize,
+    },
+    /// We are receiving a file from the client.
+    ///
+    /// Data is written to the file as it arrives.
+    Incoming {
+        /// The path the file is being stored at.
+        path: PathBuf,
+        /// How the file is being stored.
+        mode: StoreMode,
+        /// Where the data goes.
+        ///
+        /// Files are usually only opened once the data connection is,
+        /// so that nothing is changed if the client never connects.
+        file: Option<IncomingFile>,
+    },
+}
+
+/// The file an upload is being written into.
+pub struct IncomingFile
+{
+    /// Where the data is being written.
+    ///
+    /// This is a temporary file when replacing a file, which is
+    /// only moved into place once the upload is complete.
+    pub path: PathBuf,
+    pub writer: Box<Write>,
+}
+
+impl Direction
+{
+    /// Sends everything from a reader to the client.
+    pub fn outgoing(reader: Box<Read>) -> Self {
+        Direction::Outgoing { reader: reader, chunk: Vec::new(), position: 0 }
+    }
+
+    /// Sends a buffer to the client.
+    pub fn outgoing_data(data: Vec<u8>) -> Self {
+        Direction::outgoing(Box::new(io::Cursor::new(data)))
+    }
+}
+
+impl fmt::Debug for Direction
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Direction::Outgoing { ref chunk, position, .. } => {
+                write!(fmt, "Outgoing {{ chunk: {} bytes, position: {} }}", chunk.len(), position)
+            },
+            Direction::Incoming { ref path, mode, ref file } => {
+                write!(fmt, "Incoming {{ path: {:?}, mode: {:?}, open: {} }}", path, mode, file.is_some())
+            },
+        }
+    }
+}
+
+/// How an uploaded file is stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StoreMode
+{
+    /// Create the file, replacing any existing one ('STOR').
+    Replace,
+    /// Append to the end of the file if it exists ('APPE').
+    Append,
+    /// Create a file under a name we picked ourselves ('STOU').
+    Unique,
+    /// Resume an upload, replacing everything after an offset ('REST').
+    Restart { offset: u64 },
+}
+
+impl StoreMode
+{
+    /// Opens the file that the data is written into.
+    pub fn open(&self, path: &Path, file_system: &mut FileSystem) -> Result<IncomingFile, Error> {
+        let write_mode = match *self {
+            StoreMode::Replace => return open_temporary(path, file_system),
+            StoreMode::Unique => WriteMode::CreateNew,
+            StoreMode::Append => WriteMode::Append,
+            StoreMode::Restart { offset } => WriteMode::Offset(offset),
+        };
+
+        Ok(IncomingFile {
+            path: path.to_owned(),
+            writer: file_system.open_writer(path, write_mode)?,
+        })
+    }
+
+    /// Moves a complete upload into place.
+    pub fn finish(&self, path: &Path, file: IncomingFile, file_system: &mut FileSystem)
+        -> Result<(), Error> {
+        let IncomingFile { path: written_path, writer } = file;
+        drop(writer);
+
+        if written_path == path { return Ok(()) };
+
+        match file_system.rename(&written_path, path) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                file_system.remove_file(&written_path).ok();
+                Err(e)
+            },
+        }
+    }
+
+    /// Throws away an unfinished upload.
+    ///
+    /// Appending to a file or resuming an upload keeps what we have
+    /// received, so that the client can resume it from there.
+    pub fn discard(&self, file: IncomingFile, file_system: &mut FileSystem) {
+        let IncomingFile { path, writer } = file;
+        drop(writer);
+
+        match *self {
+            StoreMode::Replace | StoreMode::Unique => {
+                debug!("removing partial upload {}", path.display());
+
+                if let Err(e) = file_system.remove_file(&path) {
+                    warn!("could not remove partial upload {}: {}", path.display(), e);
+                }
+            },
+            StoreMode::Append | StoreMode::Restart { .. } => (),
+        }
+    }
+}
+
+/// Creates a temporary file to upload a file into, next to where
+/// it will end up.
+fn open_temporary(path: &Path, file_system: &mut FileSystem) -> Result<IncomingFile, Error> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+    loop {
+        let temp_path = path.with_file_name(format!(".{}.{}.part", file_name, Uuid::new_v4().simple()));
+
+        match file_system.open_writer(&temp_path, WriteMode::CreateNew) {
+            Ok(writer) => return Ok(IncomingFile { path: temp_path, writer: writer }),
+            Err(Error(ErrorKind::Io(ref e), _)) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
 }
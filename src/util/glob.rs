@@ -0,0 +1,145 @@
+//! Shell-style wildcard matching of file names.
+//!
+//! Supports `*`, `?` and bracket expressions such as `[a-z]` and `[!0-9]`.
+
+/// Checks whether a string contains any wildcard characters.
+pub fn is_pattern(s: &str) -> bool {
+    s.chars().any(|c| c == '*' || c == '?' || c == '[')
+}
+
+/// Checks whether a file name matches a pattern.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    // The position to retry from after the last '*'.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some(&'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            },
+            Some(&'?') => Some(1),
+            Some(&'[') => match match_bracket(&pattern[p..], name[n]) {
+                Some((true, length)) => Some(length),
+                // An unclosed bracket matches a literal '['.
+                None if name[n] == '[' => Some(1),
+                _ => None,
+            },
+            Some(&c) if c == name[n] => Some(1),
+            _ => None,
+        };
+
+        match step {
+            Some(length) => {
+                p += length;
+                n += 1;
+            },
+            None => match backtrack {
+                // Let the last '*' swallow one more character.
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches a character against a bracket expression at the start of a pattern.
+///
+/// Returns whether it matched and the length of the expression, or
+/// `None` if the bracket is never closed.
+fn match_bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = match pattern.get(i) {
+        Some(&'!') | Some(&'^') => { i += 1; true },
+        _ => false,
+    };
+
+    let mut matched = false;
+    let mut first = true;
+
+    loop {
+        let start = match pattern.get(i) {
+            // A ']' straight after the opening bracket is a literal.
+            Some(&']') if !first => return Some((matched != negated, i + 1)),
+            Some(&start) => start,
+            None => return None,
+        };
+
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some(&'-'), Some(&end)) if end != ']' => {
+                matched |= start <= c && c <= end;
+                i += 3;
+            },
+            _ => {
+                matched |= start == c;
+                i += 1;
+            },
+        }
+
+        first = false;
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    pub use super::*;
+
+    #[test]
+    fn correctly_matches_literals() {
+        assert!(matches("foo.txt", "foo.txt"));
+        assert!(!matches("foo.txt", "foo.csv"));
+        assert!(!matches("foo", "foo.txt"));
+    }
+
+    #[test]
+    fn correctly_matches_stars() {
+        assert!(matches("*.csv", "data.csv"));
+        assert!(matches("*.csv", ".csv"));
+        assert!(matches("*", "anything"));
+        assert!(matches("a*b*c", "aXXbYYbc"));
+        assert!(!matches("*.csv", "data.csv.bak"));
+    }
+
+    #[test]
+    fn correctly_matches_question_marks() {
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file.txt"));
+    }
+
+    #[test]
+    fn correctly_matches_brackets() {
+        assert!(matches("file[0-9].txt", "file5.txt"));
+        assert!(!matches("file[0-9].txt", "fileX.txt"));
+        assert!(matches("file[!0-9].txt", "fileX.txt"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[ab-]", "-"));
+    }
+
+    #[test]
+    fn treats_unclosed_brackets_as_literals() {
+        assert!(matches("file[0", "file[0"));
+        assert!(!matches("file[0", "file0"));
+    }
+
+    #[test]
+    fn correctly_detects_patterns() {
+        assert!(is_pattern("*.csv"));
+        assert!(!is_pattern("data.csv"));
+    }
+}
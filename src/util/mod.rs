@@ -1,4 +1,4 @@
 //! Various utilities.
 
+pub mod glob;
 pub mod log;
-